@@ -1,14 +1,32 @@
 use std::collections::VecDeque;
 
-use bot::{Bot, PathFinderBot};
-use egui_macroquad::{egui::{self, Align2, Slider, Window}, macroquad::prelude::*};
-use map::{cell::CellType, GameMap, Move};
+use bot::{belief::GeneralBelief, tactics, Bot, BotConfig, BotKind, Personality};
+use egui_macroquad::{egui::{self, Align2, ComboBox, Slider, Window}, macroquad::prelude::*};
+use map::{cell::CellType, rules::{GameRules, Handicap, Objective, TimeLimit, VisionShape}, topology::Topology, view::PlayerView, Action, GameMap, Move, PlayerStatistics, Structure};
 
 use crate::constants::*;
 
 pub mod map;
 pub mod bot;
 
+#[derive(Clone)]
+struct SeatConfig {
+    bot: BotConfig,
+    strength: f64,
+    // Index into PLAYER_COLORS
+    color: usize,
+}
+
+impl SeatConfig {
+    fn new(id: usize) -> SeatConfig {
+        SeatConfig {
+            bot: BotConfig::default(),
+            strength: 100.0,
+            color: id % PLAYER_COLORS.len(),
+        }
+    }
+}
+
 #[derive(Clone)]
 struct GameParams {
     n: usize,
@@ -22,8 +40,14 @@ struct GameParams {
     mountain_texture: Texture2D,
     general_texture: Texture2D,
     disable_fog_of_war: bool,
+    // Suggest moves in local fights
+    tactical_hint: bool,
+    // Shade hidden cells by the chance of an enemy general being there
+    belief_overlay: bool,
     ticks_per_second: f64,
-    bots_strength: f64,
+    // Seat 0 belongs to the human player, the rest are bots
+    seats: Vec<SeatConfig>,
+    rules: GameRules,
 }
 
 impl GameParams {
@@ -32,6 +56,19 @@ impl GameParams {
         self.screen_height = screen_height();
         self.screen_min_res = self.screen_width.min(self.screen_height);
     }
+
+    // Keeps one seat per player, new seats get their own colour
+    pub fn sync_seats(&mut self) {
+        self.seats.truncate(self.players_num);
+        while self.seats.len() < self.players_num {
+            self.seats.push(SeatConfig::new(self.seats.len()));
+        }
+    }
+
+    pub fn player_color(&self, id: usize) -> Color {
+        let index = self.seats.get(id).map_or(id, |seat| seat.color);
+        PLAYER_COLORS[index % PLAYER_COLORS.len()]
+    }
 }
 
 impl Default for GameParams {
@@ -48,10 +85,87 @@ impl Default for GameParams {
             mountain_texture: Texture2D::from_file_with_format(include_bytes!("../assets/sprites/mountain.png"), Some(ImageFormat::Png)),
             general_texture: Texture2D::from_file_with_format(include_bytes!("../assets/sprites/general.png"), Some(ImageFormat::Png)),
             disable_fog_of_war: false,
+            tactical_hint: false,
+            belief_overlay: false,
             ticks_per_second: 3.0,
-            bots_strength: 100.0,
+            seats: (0..2).map(SeatConfig::new).collect(),
+            rules: GameRules::default(),
+        }
+    }
+}
+
+// Screen placement of the map cells
+struct MapLayout {
+    topology: Topology,
+    cell_size: f32,
+    x_offset: f32,
+    y_offset: f32,
+}
+
+impl MapLayout {
+    fn new(params: &GameParams, topology: Topology) -> MapLayout {
+        let (world_width, world_height) = topology.world_size(params.n, params.m);
+        let cell_size = (params.screen_height / world_height).min(params.screen_width / world_width) * 0.95;
+        MapLayout {
+            topology,
+            cell_size,
+            x_offset: (params.screen_width - cell_size * world_width) * 0.5,
+            y_offset: (params.screen_height - cell_size * world_height) * 0.5,
         }
     }
+
+    fn to_screen(&self, (wx, wy): (f32, f32)) -> (f32, f32) {
+        (self.x_offset + wx * self.cell_size, self.y_offset + wy * self.cell_size)
+    }
+
+    fn center(&self, y: usize, x: usize) -> (f32, f32) {
+        self.to_screen(self.topology.cell_center(y as i64, x as i64))
+    }
+
+    // Top left corner of the square box that holds the cell contents
+    fn cell_box(&self, y: usize, x: usize) -> (f32, f32) {
+        let (cx, cy) = self.center(y, x);
+        (cx - self.cell_size * 0.5, cy - self.cell_size * 0.5)
+    }
+
+    fn fill_cell(&self, y: usize, x: usize, color: Color) {
+        match self.topology {
+            Topology::Square | Topology::Octile => {
+                let (x1, y1) = self.cell_box(y, x);
+                draw_rectangle(x1, y1, self.cell_size, self.cell_size, color);
+            },
+            Topology::Hex => {
+                let (cx, cy) = self.to_screen(self.topology.cell_center(y as i64, x as i64));
+                draw_hexagon(cx, cy, self.cell_size / 3f32.sqrt(), 0.0, true, color, color);
+            },
+        }
+    }
+
+    fn outline_cell(&self, y: usize, x: usize, thickness: f32, color: Color) {
+        match self.topology {
+            Topology::Square | Topology::Octile => {
+                let (x1, y1) = self.cell_box(y, x);
+                draw_rectangle_lines(x1, y1, self.cell_size, self.cell_size, thickness, color);
+            },
+            Topology::Hex => {
+                let (cx, cy) = self.to_screen(self.topology.cell_center(y as i64, x as i64));
+                draw_poly_lines(cx, cy, 6, self.cell_size / 3f32.sqrt(), 90.0, thickness, color);
+            },
+        }
+    }
+
+    fn cell_at(&self, n: usize, m: usize, (px, py): (f32, f32)) -> Option<(usize, usize)> {
+        let (y, x) = self.topology.cell_at((px - self.x_offset) / self.cell_size, (py - self.y_offset) / self.cell_size);
+        if y < 0 || x < 0 || y >= n as i64 || x >= m as i64 {
+            return None;
+        }
+        Some((y as usize, x as usize))
+    }
+}
+
+fn to_egui_color(color: Color) -> egui::Color32 {
+    let [r, g, b, a]: [u8; 4] = color.into();
+    egui::Color32::from_rgba_unmultiplied(r, g, b, a)
 }
 
 trait Scene {
@@ -60,59 +174,122 @@ trait Scene {
 
 struct GameScene {
     map: GameMap,
+    // What every player remembers about the map, bots only get views built from it
+    memories: Vec<GameMap>,
     params: GameParams,
     selected_cell: Option<(usize, usize)>,
-    moves_queue: VecDeque<Move>,
+    general_move_pending: bool,
+    hint: Option<Move>,
+    // Where the human player could expect enemy generals
+    belief: GeneralBelief,
+    moves_queue: VecDeque<Action>,
     bots: Vec<Box<dyn Bot>>,
     player_color: usize,
     last_tick_time: f64,
+    start_time: f64,
 }
 
 impl GameScene {
-    pub fn new(params: GameParams) -> GameScene {
+    pub fn new(mut params: GameParams) -> GameScene {
+        params.sync_seats();
         let player_color = 0;
         // let player_color = fastrand::usize(0..params.players_num);
-        let map = GameMap::new_random(params.n, params.m, params.players_num);
+        let map = GameMap::new_random(params.n, params.m, params.players_num, params.rules.clone());
+        let memories: Vec<GameMap> = (0..params.players_num)
+            .map(|id| {
+                let mut memory = map.new_memory(id);
+                memory.update_from(&map);
+                memory
+            })
+            .collect();
         GameScene {
-            bots: (0..params.players_num).map(|i| Box::new(PathFinderBot::from_map(&map, i)) as Box<dyn Bot>).collect(),
+            belief: GeneralBelief::new(&PlayerView::new(&map, &memories[player_color])),
+            bots: (0..params.players_num)
+                .map(|id| params.seats[id].bot.create(&PlayerView::new(&map, &memories[id])))
+                .collect(),
             player_color,
+            memories,
             map,
             params: params,
             selected_cell: None,
+            general_move_pending: false,
+            hint: None,
             moves_queue: VecDeque::new(),
             last_tick_time: -100.0,
+            start_time: get_time(),
+        }
+    }
+
+    fn remaining_seconds(&self) -> Option<f64> {
+        let rules = &self.map.rules;
+        if !rules.timed_match || rules.time_limit != TimeLimit::Minutes {
+            return None;
+        }
+        Some((rules.minutes_limit as f64 * 60.0 - (get_time() - self.start_time)).max(0.0))
+    }
+
+    fn show_countdown(&self, ui: &mut egui::Ui) {
+        if !self.map.rules.timed_match {
+            return;
+        }
+        match self.remaining_seconds() {
+            Some(seconds) => {
+                let seconds = seconds.ceil() as u32;
+                ui.label(format!("Осталось времени: {}:{:02}", seconds / 60, seconds % 60));
+            },
+            None => {
+                ui.label(format!("Осталось ходов: {}", self.map.rules.turn_limit.saturating_sub(self.map.turn)));
+            },
         }
     }
 
     fn draw_game_map(&self) {
-        let cell_size = (self.params.screen_height / self.params.n as f32).min(self.params.screen_width / self.params.m as f32) * 0.95;
-        let map_height = cell_size * self.params.n as f32;
-        let map_width = cell_size * self.params.m as f32;
-        let map_y_offset = (self.params.screen_height - map_height) * 0.5;
-        let map_x_offset = (self.params.screen_width - map_width) * 0.5;
+        let layout = MapLayout::new(&self.params, self.map.rules.topology);
+        let cell_size = layout.cell_size;
 
+        let supply = if self.map.rules.attrition {
+            self.map.supply_distances(self.player_color)
+        } else {
+            vec![]
+        };
+        // Hidden walls are only known from memory
+        let walls = if self.params.disable_fog_of_war { &self.map.walls } else { &self.memories[self.player_color].walls };
+        let max_belief = (0..self.params.n)
+            .flat_map(|y| (0..self.params.m).map(move |x| (y, x)))
+            .map(|(y, x)| self.belief.enemy_general_probability(y, x))
+            .fold(0.0, f64::max);
         for y in 0..self.params.n {
             for x in 0..self.params.m {
-                let x1 = x as f32 * cell_size + map_x_offset;
-                let y1 = y as f32 * cell_size + map_y_offset;
-                let cell = if self.params.disable_fog_of_war { 
-                    self.map.grid[y][x] 
-                } else { 
+                let (x1, y1) = layout.cell_box(y, x);
+                let visible = self.params.disable_fog_of_war || self.map.is_visible_to(y, x, self.player_color);
+                let remembered = !visible && self.memories[self.player_color].grid[y][x].last_update_time > 0;
+                let cell = if visible {
+                    self.map.grid[y][x]
+                } else if remembered {
+                    self.memories[self.player_color].grid[y][x]
+                } else {
                     self.map.get_with_fog(y, x, self.player_color)
                 };
+                let alpha = if remembered { 0.5 } else { 1.0 };
                 let color = match cell.owner {
-                    Some(id) => PLAYER_COLORS[id % PLAYER_COLORS.len()],
+                    Some(id) => Color { a: alpha, ..self.params.player_color(id) },
+                    None if visible && self.map.barbarians.contains(&(y, x)) => BARBARIAN_COLOR,
                     None => WHITE,
                 };
-                draw_rectangle(x1, y1, cell_size, cell_size, color);
+                layout.fill_cell(y, x, color);
+                let texture_color = Color::new(1.0, 1.0, 1.0, alpha);
                 match cell.cell_type {
                     CellType::Empty => {},
-                    CellType::Mountains => draw_texture_ex(self.params.mountain_texture, x1, y1, WHITE, DrawTextureParams {
+                    CellType::Mountains => draw_texture_ex(self.params.mountain_texture, x1, y1, texture_color, DrawTextureParams {
                         dest_size: Some(Vec2::splat(cell_size)),
                         ..Default::default()
                     }),
-                    CellType::City => draw_circle(x1 + cell_size * 0.5, y1 + cell_size * 0.5, cell_size * 0.4, BLACK),
-                    CellType::General => draw_texture_ex(self.params.general_texture, x1, y1, WHITE, DrawTextureParams {
+                    CellType::City => {
+                        let is_camp = cell.owner.is_none() && self.map.barbarian_camps.contains(&(y, x));
+                        let city_color = if is_camp { BARBARIAN_COLOR } else { BLACK };
+                        draw_circle(x1 + cell_size * 0.5, y1 + cell_size * 0.5, cell_size * 0.4, Color { a: alpha, ..city_color });
+                    },
+                    CellType::General => draw_texture_ex(self.params.general_texture, x1, y1, texture_color, DrawTextureParams {
                         dest_size: Some(Vec2::splat(cell_size)),
                         ..Default::default()
                     }),
@@ -135,85 +312,175 @@ impl GameScene {
                         color: BLACK,
                         ..Default::default()
                     });
+                    let text_color = if remembered { Color::new(0.8, 0.8, 0.8, 1.0) } else { Color::new(0.7, 0.9, 1.0, 1.0) };
                     draw_text_ex(&format!("{}", cell.army_size), x1 + text_x_offset, y1 + cell_size * 0.7, TextParams {
                         font_size: (cell_size * 0.7) as u16,
-                        color: Color::new(0.7, 0.9, 1.0, 1.0),
+                        color: text_color,
                         ..Default::default()
                     });
                 }
-                if !self.map.is_visible_to(y, x, self.player_color) {
-                    draw_rectangle(x1, y1, cell_size, cell_size, Color::new(0.3, 0.3, 0.3, 0.5));
+                if visible && cell.owner == Some(self.player_color) && self.map.is_out_of_supply(&supply, y, x) {
+                    layout.fill_cell(y, x, Color::new(1.0, 0.6, 0.0, 0.35));
+                }
+                if self.map.is_hostile(y, x) && cell.cell_type != CellType::Mountains {
+                    layout.fill_cell(y, x, Color::new(0.9, 0.1, 0.1, 0.35));
+                }
+                if !visible {
+                    layout.fill_cell(y, x, Color::new(0.3, 0.3, 0.3, 0.5));
+                }
+                if self.params.belief_overlay && !visible && max_belief > 0.0 {
+                    let probability = self.belief.enemy_general_probability(y, x) / max_belief;
+                    layout.fill_cell(y, x, Color::new(1.0, 0.0, 0.8, 0.7 * probability as f32));
+                }
+                // Turn when a remembered cell was last seen
+                if remembered && cell.cell_type != CellType::Mountains {
+                    draw_text_ex(&format!("{}", cell.last_update_time), x1 + cell_size * 0.05, y1 + cell_size * 0.25, TextParams {
+                        font_size: (cell_size * 0.25) as u16,
+                        color: WHITE,
+                        ..Default::default()
+                    });
+                }
+                if let Some(&(expiration, _)) = walls.iter().find(|&&(_, coords)| coords == (y, x)) {
+                    draw_text_ex(&format!("{}", expiration.saturating_sub(self.map.turn)), x1 + cell_size * 0.05, y1 + cell_size * 0.95, TextParams {
+                        font_size: (cell_size * 0.3) as u16,
+                        color: WHITE,
+                        ..Default::default()
+                    });
+                }
+                // Objective markers are known to everyone
+                if self.map.objective.hill == Some((y, x)) {
+                    layout.outline_cell(y, x, cell_size * 0.15, GOLD);
+                }
+                if self.map.objective.flag == Some((y, x)) {
+                    let pole_x = x1 + cell_size * 0.8;
+                    draw_line(pole_x, y1 + cell_size * 0.1, pole_x, y1 + cell_size * 0.5, 2.0, BLACK);
+                    draw_triangle(
+                        vec2(pole_x, y1 + cell_size * 0.1),
+                        vec2(pole_x, y1 + cell_size * 0.3),
+                        vec2(pole_x - cell_size * 0.25, y1 + cell_size * 0.2),
+                        GOLD,
+                    );
                 }
                 if Some((y, x)) == self.selected_cell {
-                    draw_rectangle(x1, y1, cell_size, cell_size, Color::new(0.2, 0.4, 0.8, 0.4));
+                    layout.fill_cell(y, x, Color::new(0.2, 0.4, 0.8, 0.4));
                 }
             }
         }
 
         // Drawing black lines between cells
-        for y in 0..=self.params.n {
-            let x1 = map_x_offset;
-            let y1 = y as f32 * cell_size + map_y_offset;
-            draw_line(x1, y1, x1 + map_width, y1, 3.0, BLACK);
+        match layout.topology {
+            Topology::Square | Topology::Octile => {
+                let (map_width, map_height) = (cell_size * self.params.m as f32, cell_size * self.params.n as f32);
+                for y in 0..=self.params.n {
+                    let x1 = layout.x_offset;
+                    let y1 = y as f32 * cell_size + layout.y_offset;
+                    draw_line(x1, y1, x1 + map_width, y1, 3.0, BLACK);
+                }
+                for x in 0..=self.params.m {
+                    let x1 = x as f32 * cell_size + layout.x_offset;
+                    let y1 = layout.y_offset;
+                    draw_line(x1, y1, x1, y1 + map_height, 3.0, BLACK);
+                }
+            },
+            Topology::Hex => {
+                for y in 0..self.params.n {
+                    for x in 0..self.params.m {
+                        layout.outline_cell(y, x, 3.0, BLACK);
+                    }
+                }
+            },
+        }
+
+        // Suggested move in a local fight
+        if let Some(Move { from, to }) = self.hint {
+            let (x1, y1) = layout.center(from.0, from.1);
+            let (x2, y2) = layout.center(to.0, to.1);
+            draw_line(x1, y1, x2, y2, cell_size * 0.12, ORANGE);
+            draw_circle(x2, y2, cell_size * 0.15, ORANGE);
         }
-        for x in 0..=self.params.m {
-            let x1 = x as f32 * cell_size + map_x_offset;
-            let y1 = map_y_offset;
-            draw_line(x1, y1, x1, y1 + map_height, 3.0, BLACK);
+
+        // Safe zone border
+        if self.map.rules.shrinking_zone {
+            let rings = self.map.hostile_rings();
+            if 2 * rings < self.params.n.min(self.params.m) {
+                let topology = layout.topology;
+                let row_offset = topology.cell_center(rings as i64, 0).1 - topology.cell_center(0, 0).1;
+                let (x1, y1) = layout.to_screen((rings as f32, row_offset));
+                let (zone_width, zone_height) = topology.world_size(self.params.n - 2 * rings, self.params.m - 2 * rings);
+                draw_rectangle_lines(x1, y1, zone_width * cell_size, zone_height * cell_size, 6.0, RED);
+            }
         }
     }
 
     fn process_input(&mut self) {
-        if is_key_released(KeyCode::E) {
+        // Q, E, Z and C are diagonal moves when diagonals are allowed
+        let diagonals = self.map.rules.topology == Topology::Octile;
+        if is_key_released(KeyCode::Escape) || (!diagonals && is_key_released(KeyCode::E)) {
             self.selected_cell = None;
             return;
         }
-        if is_key_released(KeyCode::Q) {
+        if is_key_released(KeyCode::Backspace) || (!diagonals && is_key_released(KeyCode::Q)) {
             self.moves_queue.clear();
             return;
         }
+        if is_key_released(KeyCode::G) && self.map.rules.mobile_generals {
+            self.general_move_pending = !self.general_move_pending;
+            return;
+        }
         if let Some((cy, cx)) = self.selected_cell {
+            if self.map.rules.buildings {
+                let structure = if is_key_released(KeyCode::B) {
+                    Some(Structure::City)
+                } else if is_key_released(KeyCode::V) {
+                    Some(Structure::Wall)
+                } else {
+                    None
+                };
+                if let Some(structure) = structure {
+                    self.moves_queue.push_back(Action::Build((cy, cx), structure));
+                    return;
+                }
+            }
+            // On a hex grid W and S lead to the upper and lower neighbours of the same column
             let delta = if is_key_released(KeyCode::W) {
-                Some((usize::MAX, 0))
+                Some((-1, 0))
             } else if is_key_released(KeyCode::S) {
                 Some((1, 0))
             } else if is_key_released(KeyCode::A) {
-                Some((0, usize::MAX))
+                Some((0, -1))
             } else if is_key_released(KeyCode::D) {
                 Some((0, 1))
+            } else if diagonals && is_key_released(KeyCode::Q) {
+                Some((-1, -1))
+            } else if diagonals && is_key_released(KeyCode::E) {
+                Some((-1, 1))
+            } else if diagonals && is_key_released(KeyCode::Z) {
+                Some((1, -1))
+            } else if diagonals && is_key_released(KeyCode::C) {
+                Some((1, 1))
             } else {
                 None
             };
-            if let Some((dy, dx)) = delta {
-                let ny = cy.wrapping_add(dy);
-                let nx = cx.wrapping_add(dx);
+            // Moves past the edge wrap around on a torus and are rejected otherwise
+            let target = delta.and_then(|(dy, dx)| self.map.normalize((cy as i64 + dy, cx as i64 + dx)));
+            if let Some((ny, nx)) = target {
                 let mv = Move::new(cy, cx, ny, nx);
                 if self.map.could_become_a_valid_move(mv) {
-                    self.moves_queue.push_back(mv); 
+                    if self.general_move_pending {
+                        self.moves_queue.push_back(Action::MoveGeneral(mv));
+                    } else {
+                        self.moves_queue.push_back(Action::Move(mv)); 
+                    }
                 }
+                self.general_move_pending = false;
                 self.selected_cell = Some((ny, nx));
             }
         }
         if !is_mouse_button_released(MouseButton::Left) {
             return;
         }
-        let cell_size = (self.params.screen_height / self.params.n as f32).min(self.params.screen_width / self.params.m as f32) * 0.95;
-        let map_height = cell_size * self.params.n as f32;
-        let map_width = cell_size * self.params.m as f32;
-        let map_y_offset = (self.params.screen_height - map_height) * 0.5;
-        let map_x_offset = (self.params.screen_width - map_width) * 0.5;
-        let mut selected_cell = None;
-        let (mouse_x, mouse_y) = mouse_position();
-        for y in 0..self.params.n {
-            for x in 0..self.params.m {
-                let y1 = map_y_offset + cell_size * y as f32;
-                let x1 = map_x_offset + cell_size * x as f32;
-                if x1 <= mouse_x && mouse_x < x1 + cell_size && y1 <= mouse_y && mouse_y < y1 + cell_size {
-                    selected_cell = Some((y, x));
-                }
-            }
-        }
-        let Some(selected_cell) = selected_cell else {
+        let layout = MapLayout::new(&self.params, self.map.rules.topology);
+        let Some(selected_cell) = layout.cell_at(self.params.n, self.params.m, mouse_position()) else {
             return;
         };
         // First selection
@@ -227,36 +494,84 @@ impl GameScene {
             to: selected_cell,
         };
         if self.map.could_become_a_valid_move(mv) {
-            self.moves_queue.push_back(mv); 
+            self.moves_queue.push_back(Action::Move(mv)); 
         }
         self.selected_cell = Some(selected_cell);
     }
 
+    fn player_name(&self, id: usize) -> String {
+        if id == self.player_color {
+            "Вы".to_string()
+        } else {
+            format!("Бот {}", id)
+        }
+    }
+
+    fn show_objective(&self, ui: &mut egui::Ui) {
+        match self.map.rules.objective {
+            Objective::Conquest => {},
+            Objective::KingOfTheHill => {
+                let holder = match self.map.objective.hill_holder {
+                    Some(id) => self.player_name(id),
+                    None => "никто".to_string(),
+                };
+                ui.label(format!("Гору удерживает: {}", holder));
+                let progress = self.map.objective.hill_held_turns as f32 / self.map.rules.hill_hold_turns as f32;
+                ui.add(egui::ProgressBar::new(progress).text(format!(
+                    "{}/{}",
+                    self.map.objective.hill_held_turns, self.map.rules.hill_hold_turns
+                )));
+            },
+            Objective::CaptureTheFlag => {
+                let (Some(carrier), Some((fy, fx))) = (self.map.flag_carrier(), self.map.objective.flag) else {
+                    ui.label("Флаг ничей");
+                    return;
+                };
+                ui.label(format!("Флаг у: {}", self.player_name(carrier)));
+                let mut dist = usize::MAX;
+                for y in 0..self.map.n {
+                    for x in 0..self.map.m {
+                        let cell = self.map.grid[y][x];
+                        if cell.owner == Some(carrier) && cell.cell_type == CellType::General {
+                            dist = dist.min(self.map.distance((y, x), (fy, fx)));
+                        }
+                    }
+                }
+                let progress = 1.0 - dist as f32 / (self.map.n + self.map.m) as f32;
+                ui.add(egui::ProgressBar::new(progress.max(0.0)).text(format!("До генерала {} клеток", dist)));
+            },
+        }
+    }
+
     fn next_tick(&mut self) {
         for id in 0..self.params.players_num {
             assert_eq!(self.map.curr_color, id);
+            if !self.map.can_act(id) {
+                self.map.skip_turn();
+                continue;
+            }
             if id == self.player_color {
-                let Some(&next_move) = self.moves_queue.front() else {
+                let Some(&next_action) = self.moves_queue.front() else {
                     self.map.skip_turn();
                     continue;
                 };
                 self.moves_queue.pop_front().unwrap();
-                if self.map.is_a_valid_move(next_move) {
-                    self.map.make_move(next_move);
+                if self.map.is_a_valid_action(next_action) {
+                    self.map.make_action(next_action);
                 } else {
                     self.map.skip_turn();
-                    println!("Incorrect move {:?}", next_move);
+                    println!("Incorrect move {:?}", next_action);
                 }
             } else {
                 let bot = &mut self.bots[id];
-                let Some(best_move) = bot.get_best_move(self.params.bots_strength) else {
+                let Some(best_action) = bot.get_best_action(self.params.seats[id].strength) else {
                     self.map.skip_turn();
                     continue;
                 };
-                if self.map.is_a_valid_move(best_move) {
-                    self.map.make_move(best_move);
+                if self.map.is_a_valid_action(best_action) {
+                    self.map.make_action(best_action);
                 } else {
-                    println!("Bad bot move! {:?}", best_move);
+                    println!("Bad bot move! {:?}", best_action);
                     self.map.skip_turn();
                 }
             }
@@ -266,9 +581,17 @@ impl GameScene {
                 self.map.grid[y][x].last_update_time = self.map.turn;
             }
         }
+        self.hint = None;
         for id in 0..self.params.players_num {
-            if id != self.player_color {
-                self.bots[id].update_from_map(&self.map);
+            self.memories[id].update_from(&self.map);
+            if id == self.player_color && self.params.tactical_hint {
+                self.hint = tactics::tactical_move(&self.memories[id], id);
+            }
+            let view = PlayerView::new(&self.map, &self.memories[id]);
+            if id == self.player_color {
+                self.belief.update(&view);
+            } else {
+                self.bots[id].update_from_view(&view);
             }
         }
     }
@@ -283,14 +606,60 @@ impl Scene for GameScene {
             Window::new("Меню")
                 .show(egui_ctx, |ui| {
                     if ui.button("Новая игра").clicked() {
-                        next_scene = Some(Box::new(MenuScene { params: self.params.clone() }));
+                        next_scene = Some(Box::new(MenuScene::new(self.params.clone())));
                     }
                     ui.label("Ходов в секунду");
                     ui.add(Slider::new(&mut self.params.ticks_per_second, 0.2..=20.0).logarithmic(true));
-                    ui.label("Интеллект ботов (%)");
-                    ui.add(Slider::new(&mut self.params.bots_strength, 0.0..=100.0));
+                    ui.collapsing("Интеллект ботов (%)", |ui| {
+                        for id in (0..self.params.players_num).filter(|&id| id != self.player_color) {
+                            ui.colored_label(to_egui_color(self.params.player_color(id)), self.player_name(id));
+                            ui.add(Slider::new(&mut self.params.seats[id].strength, 0.0..=100.0));
+                        }
+                    });
                     ui.checkbox(&mut self.params.disable_fog_of_war, "Отключить туман войны");
+                    ui.checkbox(&mut self.params.tactical_hint, "Подсказки в ближнем бою");
+                    ui.checkbox(&mut self.params.belief_overlay, "Где могут быть генералы врагов");
+                    if let Some(turns) = self.map.turns_until_zone_shrinks() {
+                        ui.label(format!("Зона сузится через {} ходов", turns));
+                    }
+                    self.show_objective(ui);
+                    self.show_countdown(ui);
+                    if self.general_move_pending {
+                        ui.label("Выберите направление для генерала");
+                    }
+                    if !self.map.can_act(self.player_color) {
+                        let start_turn = self.map.rules.handicap(self.player_color).start_turn;
+                        ui.label(format!("Вы вступите в игру на ходу {}", start_turn));
+                    }
                 });
+            if !self.map.rules.silent_war {
+                let stats = self.map.get_statistics();
+                let mut ids: Vec<usize> = (0..self.map.players_num).collect();
+                ids.sort_by_key(|&id| (-stats[id].total_army, -stats[id].total_fields));
+                let show_handicaps = (0..self.map.players_num).any(|id| self.map.rules.handicap(id) != Handicap::default());
+                Window::new("Таблица лидеров")
+                    .anchor(Align2::RIGHT_TOP, egui::Vec2::ZERO)
+                    .show(egui_ctx, |ui| {
+                        egui::Grid::new("leaderboard").striped(true).show(ui, |ui| {
+                            ui.label("Игрок");
+                            ui.label("Армия");
+                            ui.label("Земля");
+                            if show_handicaps {
+                                ui.label("Гандикап");
+                            }
+                            ui.end_row();
+                            for id in ids {
+                                ui.colored_label(to_egui_color(self.params.player_color(id)), self.player_name(id));
+                                ui.label(format!("{}", stats[id].total_army));
+                                ui.label(format!("{}", stats[id].total_fields));
+                                if show_handicaps {
+                                    ui.label(self.map.rules.handicap(id).describe());
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+            }
         });
 
         self.draw_game_map();
@@ -300,15 +669,94 @@ impl Scene for GameScene {
             self.next_tick();
             self.last_tick_time = get_time();
         }
+        if self.remaining_seconds() == Some(0.0) {
+            self.map.finish_by_score();
+        }
+        if self.map.winner.is_some() {
+            next_scene = Some(Box::new(ResultsScene::new(self)));
+        }
         
         egui_macroquad::draw();
         next_scene
     }
 }
 
+struct ResultsScene {
+    params: GameParams,
+    winner: String,
+    rows: Vec<(String, Color, PlayerStatistics, f64)>,
+}
+
+impl ResultsScene {
+    fn new(game: &GameScene) -> ResultsScene {
+        let stats = game.map.get_statistics();
+        let rows = game
+            .map
+            .ranking()
+            .into_iter()
+            .map(|(id, score)| (game.player_name(id), game.params.player_color(id), stats[id].clone(), score))
+            .collect();
+        ResultsScene {
+            params: game.params.clone(),
+            winner: game.map.winner.map(|id| game.player_name(id)).unwrap_or_default(),
+            rows,
+        }
+    }
+}
+
+impl Scene for ResultsScene {
+    fn process_frame_and_get_next_scene(&mut self) -> Option<Box<dyn Scene>> {
+        let mut next_scene: Option<Box<dyn Scene>> = None;
+        self.params.update_screen_info();
+        egui_macroquad::ui(|egui_ctx| {
+            egui_ctx.set_pixels_per_point(self.params.screen_min_res * UI_SCALE_COEFFICIENT * self.params.ui_scale);
+            Window::new("Игра окончена")
+                .anchor(Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .collapsible(false)
+                .resizable(false)
+                .show(egui_ctx, |ui| {
+                    ui.label(format!("Победитель: {}", self.winner));
+                    egui::Grid::new("results").striped(true).show(ui, |ui| {
+                        for header in ["Место", "Игрок", "Земля", "Армия", "Города", "Генералы", "Очки"] {
+                            ui.label(header);
+                        }
+                        ui.end_row();
+                        for (place, (name, color, stats, score)) in self.rows.iter().enumerate() {
+                            ui.label(format!("{}", place + 1));
+                            ui.colored_label(to_egui_color(*color), name);
+                            ui.label(format!("{}", stats.total_fields));
+                            ui.label(format!("{}", stats.total_army));
+                            ui.label(format!("{}", stats.total_cities));
+                            ui.label(format!("{}", stats.generals_captured));
+                            ui.label(format!("{:.0}", score));
+                            ui.end_row();
+                        }
+                    });
+                    if ui.button("Новая игра").clicked() {
+                        next_scene = Some(Box::new(MenuScene::new(self.params.clone())));
+                    }
+                });
+        });
+
+        egui_macroquad::draw();
+        next_scene
+    }
+}
+
 #[derive(Default)]
 struct MenuScene {
     params: GameParams,
+    // Result of the last script reload for every seat
+    program_errors: Vec<Option<Result<(), String>>>,
+}
+
+impl MenuScene {
+    fn new(params: GameParams) -> MenuScene {
+        MenuScene {
+            params,
+            program_errors: vec![],
+        }
+    }
 }
 
 impl Scene for MenuScene {
@@ -322,22 +770,237 @@ impl Scene for MenuScene {
                 .collapsible(false)
                 .resizable(false)
                 .show(egui_ctx, |ui| {
-                    ui.label("Ширина поля");
-                    ui.add(Slider::new(&mut self.params.m, 10..=50));
-                    ui.label("Высота поля");
-                    ui.add(Slider::new(&mut self.params.n, 10..=50));
-                    ui.label("Количество игроков");
-                    ui.add(Slider::new(&mut self.params.players_num, 2..=16));
-                    ui.label("Ходов в секунду");
-                    ui.add(Slider::new(&mut self.params.ticks_per_second, 0.2..=20.0).logarithmic(true));
-                    ui.label("Интеллект ботов (%)");
-                    ui.add(Slider::new(&mut self.params.bots_strength, 0.0..=100.0));
-                    // Ui scale slider
-                    ui.label("Масштаб интерфейса");
-                    let response = ui.add(Slider::new(&mut self.params.new_ui_scale, 0.3..=2.0));
-                    if response.drag_released() {
-                        self.params.ui_scale = self.params.new_ui_scale;
-                    }
+                    // The list of settings is taller than small screens
+                    let max_height = ui.ctx().available_rect().height() * 0.8;
+                    egui::ScrollArea::vertical().max_height(max_height).show(ui, |ui| {
+                        ui.label("Ширина поля");
+                        ui.add(Slider::new(&mut self.params.m, 10..=50));
+                        ui.label("Высота поля");
+                        ui.add(Slider::new(&mut self.params.n, 10..=50));
+                        ui.label("Количество игроков");
+                        ui.add(Slider::new(&mut self.params.players_num, 2..=16));
+                        ui.label("Ходов в секунду");
+                        ui.add(Slider::new(&mut self.params.ticks_per_second, 0.2..=20.0).logarithmic(true));
+                        // Seats
+                        self.params.sync_seats();
+                        ui.collapsing("Игроки", |ui| {
+                            egui::Grid::new("seats").striped(true).show(ui, |ui| {
+                                ui.label("Игрок");
+                                ui.label("Тип");
+                                ui.label("Интеллект (%)");
+                                ui.label("Характер");
+                                ui.label("Цвет");
+                                ui.end_row();
+                                for (id, seat) in self.params.seats.iter_mut().enumerate() {
+                                    if id == 0 {
+                                        ui.label("Вы");
+                                        ui.label("Человек");
+                                        ui.label("");
+                                        ui.label("");
+                                    } else {
+                                        ui.label(format!("Бот {}", id));
+                                        ComboBox::from_id_source(("bot_kind", id))
+                                            .selected_text(seat.bot.kind.name())
+                                            .show_ui(ui, |ui| {
+                                                for kind in BotKind::ALL {
+                                                    ui.selectable_value(&mut seat.bot.kind, kind, kind.name());
+                                                }
+                                            });
+                                        ui.add(Slider::new(&mut seat.strength, 0.0..=100.0));
+                                        ComboBox::from_id_source(("personality", id))
+                                            .selected_text(seat.bot.personality.name())
+                                            .show_ui(ui, |ui| {
+                                                for personality in Personality::ALL {
+                                                    ui.selectable_value(&mut seat.bot.personality, personality, personality.name());
+                                                }
+                                            });
+                                    }
+                                    let swatch = |index: usize| egui::RichText::new("■■■").color(to_egui_color(PLAYER_COLORS[index]));
+                                    ComboBox::from_id_source(("color", id))
+                                        .selected_text(swatch(seat.color))
+                                        .show_ui(ui, |ui| {
+                                            for index in 0..PLAYER_COLORS.len() {
+                                                ui.selectable_value(&mut seat.color, index, swatch(index));
+                                            }
+                                        });
+                                    ui.end_row();
+                                    if id != 0 && seat.bot.kind.is_search() {
+                                        ui.label("");
+                                        ui.horizontal(|ui| {
+                                            ui.radio_value(&mut seat.bot.limit_iterations, false, "Время");
+                                            ui.radio_value(&mut seat.bot.limit_iterations, true, "Итерации");
+                                        });
+                                        if seat.bot.limit_iterations {
+                                            ui.add(Slider::new(&mut seat.bot.iterations, 10..=5000).logarithmic(true));
+                                        } else {
+                                            ui.add(Slider::new(&mut seat.bot.time_limit_ms, 50..=bot::mcts::MAX_SEARCH_MS).suffix(" мс"));
+                                        }
+                                        ui.end_row();
+                                    }
+                                    if id != 0 && seat.bot.kind.needs_program() {
+                                        ui.label("");
+                                        ui.label("Программа");
+                                        ui.text_edit_singleline(&mut seat.bot.program);
+                                        if seat.bot.kind.has_time_limit() {
+                                            ui.label("Время на ход (мс)");
+                                            ui.add(Slider::new(&mut seat.bot.time_limit_ms, 50..=5000));
+                                        }
+                                        ui.end_row();
+                                        match self.program_errors.get(id) {
+                                            Some(Some(Ok(()))) => {
+                                                ui.label("");
+                                                ui.label("Загружено");
+                                                ui.end_row();
+                                            },
+                                            Some(Some(Err(err))) => {
+                                                ui.label("");
+                                                ui.colored_label(egui::Color32::RED, err);
+                                                ui.end_row();
+                                            },
+                                            _ => {},
+                                        }
+                                    }
+                                }
+                            });
+                            // Programs are loaded from disk on every game start, this only checks them
+                            if ui.button("Перезагрузить скрипты").clicked() {
+                                self.program_errors = self
+                                    .params
+                                    .seats
+                                    .iter()
+                                    .map(|seat| seat.bot.kind.needs_program().then(|| seat.bot.check_program()))
+                                    .collect();
+                            }
+                        });
+                        ComboBox::from_label("Клетки")
+                            .selected_text(self.params.rules.topology.name())
+                            .show_ui(ui, |ui| {
+                                for topology in Topology::ALL {
+                                    ui.selectable_value(&mut self.params.rules.topology, topology, topology.name());
+                                }
+                            });
+                        ui.checkbox(&mut self.params.rules.wrap_around, "Края поля соединены (тор)");
+                        // Vision settings
+                        ui.label("Радиус обзора");
+                        ui.add(Slider::new(&mut self.params.rules.vision_radius, 1..=5));
+                        ComboBox::from_label("Форма обзора")
+                            .selected_text(self.params.rules.vision_shape.name())
+                            .show_ui(ui, |ui| {
+                                for shape in VisionShape::ALL {
+                                    ui.selectable_value(&mut self.params.rules.vision_shape, shape, shape.name());
+                                }
+                            });
+                        ui.checkbox(&mut self.params.rules.mountains_block_vision, "Горы закрывают обзор");
+                        // Buildings
+                        ui.collapsing("Строительство", |ui| {
+                            let rules = &mut self.params.rules;
+                            ui.checkbox(&mut rules.buildings, "Включить (B - город, V - стена)");
+                            ui.label("Стоимость города");
+                            ui.add(Slider::new(&mut rules.city_cost, 5..=100));
+                            ui.label("Стоимость стены");
+                            ui.add(Slider::new(&mut rules.wall_cost, 1..=50));
+                            ui.label("Время жизни стены (ходов)");
+                            ui.add(Slider::new(&mut rules.wall_duration, 5..=200));
+                        });
+                        ui.checkbox(&mut self.params.rules.mobile_generals, "Подвижные генералы (G + направление)");
+                        ui.label("Столиц у игрока");
+                        ui.add(Slider::new(&mut self.params.rules.capitals, 1..=3));
+                        ui.collapsing("Варвары", |ui| {
+                            let rules = &mut self.params.rules;
+                            ui.checkbox(&mut rules.barbarians, "Включить");
+                            ui.label("Количество лагерей");
+                            ui.add(Slider::new(&mut rules.barbarian_camps, 1..=8));
+                            ui.label("Ходов между набегами");
+                            ui.add(Slider::new(&mut rules.barbarian_spawn_interval, 10..=100));
+                            ui.label("Размер отряда");
+                            ui.add(Slider::new(&mut rules.barbarian_army, 5..=50));
+                        });
+                        ui.collapsing("Снабжение", |ui| {
+                            let rules = &mut self.params.rules;
+                            ui.checkbox(&mut rules.attrition, "Армии вдали от городов теряют войска");
+                            ui.label("Дальность снабжения");
+                            ui.add(Slider::new(&mut rules.supply_range, 2..=20));
+                            ui.label("Минимальный размер армии для потерь");
+                            ui.add(Slider::new(&mut rules.attrition_min_army, 2..=50));
+                        });
+                        ui.checkbox(&mut self.params.rules.destructible_mountains, "Разрушаемые горы");
+                        if self.params.rules.destructible_mountains {
+                            ui.label("Стоимость разрушения горы");
+                            ui.add(Slider::new(&mut self.params.rules.mountain_cost, 5..=100));
+                        }
+                        // Modifiers
+                        ui.collapsing("Модификаторы", |ui| {
+                            ui.checkbox(&mut self.params.rules.leapfrog, "Чехарда: захват генерала забирает его армию");
+                            ui.checkbox(&mut self.params.rules.city_state, "Города-государства: захваченные города растут вдвое быстрее");
+                            ui.checkbox(&mut self.params.rules.misty_veil, "Туманная завеса: обзор только от армий больше 1");
+                            ui.checkbox(&mut self.params.rules.silent_war, "Тихая война: таблица лидеров скрыта");
+                            ui.checkbox(&mut self.params.rules.watchtower, "Сторожевые башни: города видят дальше");
+                        });
+                        // Battle royale
+                        ui.collapsing("Сужающаяся зона", |ui| {
+                            ui.checkbox(&mut self.params.rules.shrinking_zone, "Включить");
+                            ui.label("Начало сужения (ход)");
+                            ui.add(Slider::new(&mut self.params.rules.zone_start_turn, 25..=500));
+                            ui.label("Ходов между сужениями");
+                            ui.add(Slider::new(&mut self.params.rules.zone_shrink_interval, 5..=100));
+                        });
+                        ComboBox::from_label("Цель игры")
+                            .selected_text(self.params.rules.objective.name())
+                            .show_ui(ui, |ui| {
+                                for objective in Objective::ALL {
+                                    ui.selectable_value(&mut self.params.rules.objective, objective, objective.name());
+                                }
+                            });
+                        if self.params.rules.objective == Objective::KingOfTheHill {
+                            ui.label("Ходов удержания горы");
+                            ui.add(Slider::new(&mut self.params.rules.hill_hold_turns, 10..=200));
+                        }
+                        // Timed match
+                        ui.collapsing("Игра на время", |ui| {
+                            let rules = &mut self.params.rules;
+                            ui.checkbox(&mut rules.timed_match, "Включить");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut rules.time_limit, TimeLimit::Turns, "Ходы");
+                                ui.radio_value(&mut rules.time_limit, TimeLimit::Minutes, "Минуты");
+                            });
+                            match rules.time_limit {
+                                TimeLimit::Turns => ui.add(Slider::new(&mut rules.turn_limit, 50..=1000)),
+                                TimeLimit::Minutes => ui.add(Slider::new(&mut rules.minutes_limit, 1..=60)),
+                            };
+                            ui.label("Очки за клетку земли");
+                            ui.add(Slider::new(&mut rules.score_weights.land, 0.0..=10.0));
+                            ui.label("Очки за единицу армии");
+                            ui.add(Slider::new(&mut rules.score_weights.army, 0.0..=10.0));
+                            ui.label("Очки за город");
+                            ui.add(Slider::new(&mut rules.score_weights.cities, 0.0..=100.0));
+                            ui.label("Очки за захваченного генерала");
+                            ui.add(Slider::new(&mut rules.score_weights.generals_captured, 0.0..=500.0));
+                        });
+                        // Handicaps
+                        ui.collapsing("Гандикапы", |ui| {
+                            let handicaps = &mut self.params.rules.handicaps;
+                            handicaps.resize(self.params.players_num, Handicap::default());
+                            for (id, handicap) in handicaps.iter_mut().enumerate() {
+                                let name = if id == 0 { "Вы".to_string() } else { format!("Бот {}", id) };
+                                ui.collapsing(format!("{}: {}", name, handicap.describe()), |ui| {
+                                    ui.label("Дополнительная армия генерала");
+                                    ui.add(Slider::new(&mut handicap.starting_army, 0..=100));
+                                    ui.label("Множитель роста армии");
+                                    ui.add(Slider::new(&mut handicap.growth_multiplier, 1..=3));
+                                    ui.label("Дополнительный обзор");
+                                    ui.add(Slider::new(&mut handicap.extra_vision, 0..=3));
+                                    ui.label("Первый ход");
+                                    ui.add(Slider::new(&mut handicap.start_turn, 0..=100));
+                                });
+                            }
+                        });
+                        // Ui scale slider
+                        ui.label("Масштаб интерфейса");
+                        let response = ui.add(Slider::new(&mut self.params.new_ui_scale, 0.3..=2.0));
+                        if response.drag_released() {
+                            self.params.ui_scale = self.params.new_ui_scale;
+                        }
+                    });
                     // Start game button
                     if ui.button("Начать игру!").clicked() {
                         next_scene = Some(Box::new(GameScene::new(self.params.clone())));
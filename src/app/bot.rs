@@ -1,14 +1,221 @@
+pub mod belief;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod external;
+pub mod gather;
+pub mod mcts;
+pub mod opening;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod plugin;
+pub mod random;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod script;
+pub mod tactics;
+pub mod threat;
+
 use std::collections::VecDeque;
 
-use crate::constants::DIRECTIONS;
 
-use super::map::{GameMap, Move};
-use super::map::cell::{CellType, GameCell};
+use super::map::{Action, GameMap, Move, Structure};
+use super::map::cell::CellType;
+use super::map::rules::{Objective, TimeLimit};
+use super::map::view::PlayerView;
+use belief::GeneralBelief;
+#[cfg(not(target_arch = "wasm32"))]
+use external::ExternalBot;
+#[cfg(not(target_arch = "wasm32"))]
+use plugin::WasmBot;
+use mcts::{MctsBot, SearchBudget};
+use opening::OpeningPlan;
+use random::RandomBot;
+#[cfg(not(target_arch = "wasm32"))]
+use script::ScriptBot;
+use threat::Threat;
 
+// Bots only ever see the game through a `PlayerView`, so they can't look through the fog of war
 pub trait Bot {
-    fn get_best_move(&mut self, strength: f64) -> Option<Move>;
+    // Bots controlled by external programs, wasm modules and scripts play at their own strength
+    fn get_best_action(&mut self, strength: f64) -> Option<Action>;
+
+    fn update_from_view(&mut self, view: &PlayerView);
+}
+
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum BotKind {
+    #[default]
+    PathFinder,
+    Random,
+    Mcts,
+    // A separate process speaking the protocol from `external.rs`
+    #[cfg(not(target_arch = "wasm32"))]
+    External,
+    // A sandboxed WebAssembly module, see `plugin.rs`
+    #[cfg(not(target_arch = "wasm32"))]
+    Wasm,
+    // A Rhai script, see `script.rs`
+    #[cfg(not(target_arch = "wasm32"))]
+    Script,
+}
+
+impl BotKind {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub const ALL: [BotKind; 6] = [
+        BotKind::PathFinder,
+        BotKind::Random,
+        BotKind::Mcts,
+        BotKind::External,
+        BotKind::Wasm,
+        BotKind::Script,
+    ];
+    #[cfg(target_arch = "wasm32")]
+    pub const ALL: [BotKind; 3] = [BotKind::PathFinder, BotKind::Random, BotKind::Mcts];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BotKind::PathFinder => "Поиск путей",
+            BotKind::Random => "Случайный",
+            BotKind::Mcts => "Поиск по дереву (MCTS)",
+            #[cfg(not(target_arch = "wasm32"))]
+            BotKind::External => "Внешняя программа",
+            #[cfg(not(target_arch = "wasm32"))]
+            BotKind::Wasm => "Модуль WebAssembly",
+            #[cfg(not(target_arch = "wasm32"))]
+            BotKind::Script => "Скрипт Rhai",
+        }
+    }
+
+    // Wasm plugins are limited by fuel instead of time
+    pub fn has_time_limit(&self) -> bool {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            BotKind::External => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_search(&self) -> bool {
+        *self == BotKind::Mcts
+    }
+
+    // Whether the bot runs a user provided program
+    pub fn needs_program(&self) -> bool {
+        match self {
+            BotKind::PathFinder | BotKind::Random | BotKind::Mcts => false,
+            #[cfg(not(target_arch = "wasm32"))]
+            BotKind::External | BotKind::Wasm | BotKind::Script => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BotConfig {
+    pub kind: BotKind,
+    pub personality: Personality,
+    // Path to the program of bots that run user code
+    pub program: String,
+    pub time_limit_ms: u64,
+    // Search bots stop after a number of iterations instead of a time limit
+    pub limit_iterations: bool,
+    pub iterations: u32,
+}
+
+impl Default for BotConfig {
+    fn default() -> Self {
+        Self {
+            kind: BotKind::default(),
+            personality: Personality::default(),
+            program: String::new(),
+            time_limit_ms: 1000,
+            limit_iterations: false,
+            iterations: 500,
+        }
+    }
+}
+
+impl BotConfig {
+    // Loads the program again to report errors before the game starts
+    pub fn check_program(&self) -> Result<(), String> {
+        match self.kind {
+            #[cfg(not(target_arch = "wasm32"))]
+            BotKind::Script => script::check_script(&self.program),
+            #[cfg(not(target_arch = "wasm32"))]
+            BotKind::Wasm => plugin::check_module(&self.program),
+            #[cfg(not(target_arch = "wasm32"))]
+            BotKind::External => external::check_program(&self.program),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn create(&self, view: &PlayerView) -> Box<dyn Bot> {
+        match self.kind {
+            BotKind::PathFinder => Box::new(PathFinderBot::from_view(view, self.personality)),
+            BotKind::Random => Box::new(RandomBot::from_view(view)),
+            BotKind::Mcts => {
+                let budget = if self.limit_iterations {
+                    SearchBudget::Iterations(self.iterations)
+                } else {
+                    SearchBudget::TimeMs(self.time_limit_ms.min(mcts::MAX_SEARCH_MS))
+                };
+                Box::new(MctsBot::from_view(view, budget))
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            BotKind::External => {
+                let time_limit = std::time::Duration::from_millis(self.time_limit_ms);
+                Box::new(ExternalBot::spawn(view, &self.program, time_limit))
+            },
+            #[cfg(not(target_arch = "wasm32"))]
+            BotKind::Wasm => Box::new(WasmBot::load(view, &self.program)),
+            #[cfg(not(target_arch = "wasm32"))]
+            BotKind::Script => Box::new(ScriptBot::load(view, &self.program)),
+        }
+    }
+}
 
-    fn update_from_map(&mut self, map: &GameMap);
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Personality {
+    #[default]
+    Balanced,
+    // Prefers attacking enemy land
+    Aggressive,
+    // Prefers neutral land and cities
+    Expansionist,
+    // Keeps a bigger garrison at home and rarely attacks
+    Cautious,
+}
+
+impl Personality {
+    pub const ALL: [Personality; 4] = [
+        Personality::Balanced,
+        Personality::Aggressive,
+        Personality::Expansionist,
+        Personality::Cautious,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Personality::Balanced => "Сбалансированный",
+            Personality::Aggressive => "Агрессивный",
+            Personality::Expansionist => "Захватчик земель",
+            Personality::Cautious => "Осторожный",
+        }
+    }
+
+    // Multipliers for neutral and enemy targets
+    fn target_weights(&self) -> (f64, f64) {
+        match self {
+            Personality::Balanced => (1.0, 1.0),
+            Personality::Aggressive => (0.5, 3.0),
+            Personality::Expansionist => (3.0, 0.5),
+            Personality::Cautious => (1.0, 0.3),
+        }
+    }
+
+    // Generals smaller than this are rarely used as a source of army
+    fn general_garrison(&self) -> f64 {
+        match self {
+            Personality::Cautious => 30.0,
+            _ => 10.0,
+        }
+    }
 }
 
 #[derive(Default, Clone, Copy)]
@@ -32,18 +239,35 @@ impl VertexData {
 }
 
 const INF: i64 = 1e10 as i64;
+// Value of a hidden cell that surely holds an enemy general
+const SCOUT_VALUE: f64 = 2000.0;
+// Enemy cities, generals and their likely hideouts are attacked with gathered armies
+const GATHER_PRIORITY: f64 = 300.0;
+const GATHER_MOVES: usize = 10;
+// Walls are raised only when they make the attacker's way at least this much longer
+const WALL_DELAY: usize = 4;
 
 #[derive(Default)]
 pub struct PathFinderBot {
     pub map: GameMap,
+    personality: Personality,
+    supply: Vec<Vec<usize>>,
+    belief: GeneralBelief,
+    // Remaining moves of the gather plan that is being executed
+    gather: VecDeque<Move>,
+    opening: Option<OpeningPlan>,
 }
 
 impl PathFinderBot {
-    pub fn from_map(map: &GameMap, color: usize) -> Self {
-        let mut map = map.clone();
-        map.curr_color = color;
-        map.grid = vec![vec![GameCell::default(); map.m]; map.n];
-        Self { map }
+    pub fn from_view(view: &PlayerView, personality: Personality) -> Self {
+        Self {
+            map: view.map.clone(),
+            personality,
+            supply: vec![],
+            belief: GeneralBelief::new(view),
+            gather: VecDeque::new(),
+            opening: None,
+        }
     }
 
     fn find_paths(&self, map: &GameMap, start: (usize, usize)) -> Vec<Vec<VertexData>> {
@@ -66,9 +290,7 @@ impl PathFinderBot {
             if result[y][x].value <= 1 {
                 continue;
             }
-            for (dy, dx) in DIRECTIONS {
-                let ny = y.wrapping_add(dy);
-                let nx = x.wrapping_add(dx);
+            for (ny, nx) in map.neighbours(y, x) {
                 if !map.could_become_a_valid_move(Move::new(y, x, ny, nx)) {
                     continue;
                 }
@@ -81,6 +303,10 @@ impl PathFinderBot {
                 } else {
                     value_delta -= army_size;
                 }
+                // Long marches outside of supply range lose a unit on every step
+                if map.is_out_of_supply(&self.supply, ny, nx) && result[y][x].value >= map.rules.attrition_min_army {
+                    value_delta -= 1;
+                }
                 let new_value = result[y][x].value - 1 + value_delta;
                 if new_value <= 1 {
                     continue;
@@ -104,12 +330,18 @@ impl PathFinderBot {
         coords: (usize, usize),
     ) -> f64 {
         let cell = map.grid[coords.0][coords.1];
-        if cell.cell_type == CellType::Mountains || cell.is_friend || cell.last_update_time != self.map.turn {
+        if cell.cell_type == CellType::Mountains || cell.is_friend || map.is_hostile(coords.0, coords.1) {
             return -1e9;
         }
-        let priority = if cell.owner == None {
+        let (neutral_weight, enemy_weight) = self.personality.target_weights();
+        // Hidden cells are worth scouting when an enemy general is likely there
+        if cell.last_update_time != self.map.turn {
+            let probability = self.belief.enemy_general_probability(coords.0, coords.1);
+            return if probability > 0.0 { enemy_weight * SCOUT_VALUE * probability } else { -1e9 };
+        }
+        let mut priority = if cell.owner == None {
             // Without owner
-            match cell.cell_type {
+            neutral_weight * match cell.cell_type {
                 CellType::Empty => 6.0,
                 CellType::City => 250.0,
                 CellType::General => unreachable!(),
@@ -117,7 +349,7 @@ impl PathFinderBot {
             }
         } else if cell.owner != Some(map.curr_color) {
             // Enemy
-            match cell.cell_type {
+            enemy_weight * match cell.cell_type {
                 CellType::Empty => 100.0,
                 CellType::City => 1500.0,
                 CellType::General => 1e18,
@@ -127,16 +359,38 @@ impl PathFinderBot {
             // Me
             -1e9
         };
+        // Cells that are about to leave the safe zone are barely worth taking
+        let zone_closes_soon = map.turns_until_zone_shrinks().is_some_and(|turns| turns <= 10);
+        if zone_closes_soon && map.ring_of(coords.0, coords.1) == map.hostile_rings() && priority > 0.0 {
+            priority *= 0.1;
+        }
         priority
     }
 
+    fn eval_objective(
+        &self,
+        map: &GameMap,
+        start: (usize, usize),
+        coords: (usize, usize),
+    ) -> f64 {
+        let cell = map.grid[coords.0][coords.1];
+        let me = Some(map.curr_color);
+        match map.rules.objective {
+            Objective::Conquest => -1e9,
+            Objective::KingOfTheHill if map.objective.hill == Some(coords) && cell.owner != me => 2000.0,
+            Objective::KingOfTheHill => -1e9,
+            Objective::CaptureTheFlag if map.objective.flag == Some(coords) && cell.owner != me => 2000.0,
+            // Carrying the flag home wins the game
+            Objective::CaptureTheFlag if map.objective.flag == Some(start) && cell.cell_type == CellType::General && cell.owner == me => 1e12,
+            Objective::CaptureTheFlag => -1e9,
+        }
+    }
+
     fn get_all_moves(&self) -> Vec<Move> {
         let mut moves = vec![];
         for y in 0..self.map.n {
             for x in 0..self.map.m {
-                for (dy, dx) in DIRECTIONS {
-                    let ny = y.wrapping_add(dy);
-                    let nx = x.wrapping_add(dx);
+                for (ny, nx) in self.map.neighbours(y, x) {
                     let mv = Move::new(y, x, ny, nx);
                     if self.map.is_a_valid_move(mv) {
                         moves.push(mv);
@@ -151,15 +405,260 @@ impl PathFinderBot {
         let moves = self.get_all_moves();
         fastrand::choice(&moves).copied()
     }
+
+    // First move of the path found by `find_paths` from `start` to `target`
+    fn first_step(grid: &[Vec<VertexData>], start: (usize, usize), target: (usize, usize)) -> Option<Move> {
+        let mut curr_coords = target;
+        if curr_coords == start || grid[curr_coords.0][curr_coords.1].parent.0 == usize::MAX {
+            return None;
+        }
+        loop {
+            let prev_coords = grid[curr_coords.0][curr_coords.1].parent;
+            if prev_coords == start {
+                let (y2, x2) = curr_coords;
+                return Some(Move::new(start.0, start.1, y2, x2));
+            }
+            curr_coords = prev_coords;
+        }
+    }
+
+    // Steps a threatened general away from the enemy stack when generals are mobile
+    fn evacuate_general(&self, threats: &[Threat]) -> Option<Action> {
+        if !self.map.rules.mobile_generals {
+            return None;
+        }
+        let imminent = threats.iter().filter(|threat| threat.turns <= 2 && threat.is_general(&self.map));
+        for &Threat { target: (gy, gx), attacker: (ey, ex), .. } in imminent {
+            let best = self
+                .map
+                .neighbours(gy, gx)
+                .map(|(ny, nx)| Move::new(gy, gx, ny, nx))
+                .filter(|&mv| self.map.is_a_valid_general_move(mv))
+                .max_by_key(|mv| self.map.distance(mv.to, (ey, ex)));
+            if let Some(mv) = best {
+                return Some(Action::MoveGeneral(mv));
+            }
+        }
+        None
+    }
+
+    // Defend mode: a stack that can beat the attacker goes to meet it,
+    // otherwise the army around a threatened general is gathered home if it arrives in time
+    fn defend(&self, threats: &[Threat]) -> Option<Move> {
+        threats.iter().find_map(|threat| self.defend_against(threats, threat))
+    }
+
+    fn defend_against(&self, threats: &[Threat], threat: &Threat) -> Option<Move> {
+        let mut defenders: Vec<(i64, (usize, usize))> = (0..self.map.n)
+            .flat_map(|y| (0..self.map.m).map(move |x| (y, x)))
+            .filter(|&(y, x)| {
+                let cell = self.map.grid[y][x];
+                cell.owner == Some(self.map.curr_color) && cell.army_size > 1 && cell.last_update_time == self.map.turn
+            })
+            .filter(|&coords| threats.iter().all(|threat| threat.target != coords))
+            .map(|(y, x)| (-self.map.grid[y][x].army_size, (y, x)))
+            .collect();
+        defenders.sort_unstable();
+        let intercept = defenders
+            .iter()
+            .take(5)
+            .filter_map(|&(_, start)| {
+                let grid = self.find_paths(&self.map, start);
+                let info = grid[threat.attacker.0][threat.attacker.1];
+                if info.value <= 1 || info.dist > threat.turns as i64 {
+                    return None;
+                }
+                Some((info.dist, Self::first_step(&grid, start, threat.attacker)?))
+            })
+            .min_by_key(|&(dist, _)| dist);
+        if let Some((_, mv)) = intercept {
+            return Some(mv);
+        }
+        if !threat.is_general(&self.map) {
+            return None;
+        }
+        let me = self.map.curr_color;
+        let plan = gather::plan_gather(&self.map, me, threat.target, threat.turns.saturating_sub(1).max(1));
+        if plan.army < threat.excess {
+            return None;
+        }
+        plan.moves.front().copied()
+    }
+
+    // Number of moves from `start` to every cell around mountains and the `blocked` cell
+    fn route_lengths(&self, start: (usize, usize), blocked: Option<(usize, usize)>) -> Vec<Vec<usize>> {
+        let mut dist = vec![vec![usize::MAX; self.map.m]; self.map.n];
+        dist[start.0][start.1] = 0;
+        let mut queue = VecDeque::from([start]);
+        while let Some((y, x)) = queue.pop_front() {
+            for (ny, nx) in self.map.neighbours(y, x) {
+                if dist[ny][nx] != usize::MAX || self.map.grid[ny][nx].cell_type == CellType::Mountains || blocked == Some((ny, nx)) {
+                    continue;
+                }
+                dist[ny][nx] = dist[y][x] + 1;
+                queue.push_back((ny, nx));
+            }
+        }
+        dist
+    }
+
+    // A wall on the attacker's shortest way that it has to walk around when nobody can intercept it
+    fn find_wall(&self, threats: &[Threat]) -> Option<Action> {
+        if !self.map.rules.buildings {
+            return None;
+        }
+        threats.iter().find_map(|threat| {
+            let from_attacker = self.route_lengths(threat.attacker, None);
+            let to_target = self.route_lengths(threat.target, None);
+            let wall = (0..self.map.n)
+                .flat_map(|y| (0..self.map.m).map(move |x| (y, x)))
+                .filter(|&(y, x)| from_attacker[y][x].saturating_add(to_target[y][x]) == threat.turns)
+                .filter(|&coords| coords != threat.target && self.map.is_a_valid_build(coords, Structure::Wall))
+                .find(|&coords| {
+                    let detour = self.route_lengths(threat.attacker, Some(coords))[threat.target.0][threat.target.1];
+                    detour == usize::MAX || detour >= threat.turns + WALL_DELAY
+                })?;
+            Some(Action::Build(wall, Structure::Wall))
+        })
+    }
+
+    // Follows the opening plan during the first turns, a plan that went wrong is made again from the current state.
+    // `Some(None)` passes the turn to let the general grow
+    fn opening_action(&mut self) -> Option<Option<Action>> {
+        if self.map.turn >= opening::OPENING_TURNS {
+            self.opening = None;
+            return None;
+        }
+        let me = self.map.curr_color;
+        let general = (0..self.map.n)
+            .flat_map(|y| (0..self.map.m).map(move |x| (y, x)))
+            .find(|&(y, x)| self.map.grid[y][x].owner == Some(me) && self.map.grid[y][x].cell_type == CellType::General)?;
+        for replan in [false, true] {
+            if replan || self.opening.is_none() {
+                self.opening = Some(opening::plan_opening(&self.map, me, general));
+            }
+            match self.opening.as_ref()?.step(self.map.turn)? {
+                None => return Some(None),
+                Some(mv) if self.map.is_a_valid_move(mv) => return Some(Some(Action::Move(mv))),
+                Some(_) => {},
+            }
+        }
+        None
+    }
+
+    fn next_gather_move(&mut self) -> Option<Move> {
+        let mv = self.gather.pop_front()?;
+        if self.map.is_a_valid_move(mv) {
+            return Some(mv);
+        }
+        // The plan is stale once one of its cells was lost
+        self.gather.clear();
+        None
+    }
+
+    // Gathers army next to the most valuable target that no single stack can take,
+    // the plan is only started when the gathered army is enough to capture it
+    fn start_gather(&mut self) -> Option<Move> {
+        let me = self.map.curr_color;
+        let own_cells: Vec<(usize, usize)> = (0..self.map.n)
+            .flat_map(|y| (0..self.map.m).map(move |x| (y, x)))
+            .filter(|&(y, x)| {
+                let cell = self.map.grid[y][x];
+                cell.owner == Some(me) && cell.cell_type != CellType::General && cell.last_update_time == self.map.turn
+            })
+            .collect();
+        let (_, target, root, dist) = (0..self.map.n)
+            .flat_map(|y| (0..self.map.m).map(move |x| (y, x)))
+            .filter_map(|target| {
+                let priority = self.eval_target_cell(&self.map, target);
+                if priority < GATHER_PRIORITY {
+                    return None;
+                }
+                let (dist, root) = own_cells.iter().map(|&coords| (self.map.distance(coords, target), coords)).min()?;
+                Some((priority / dist as f64, target, root, dist))
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))?;
+        let plan = gather::plan_gather(&self.map, me, root, GATHER_MOVES);
+        let gathered = plan.army + self.map.grid[root.0][root.1].army_size - 1;
+        let defenders = self.map.grid[target.0][target.1].army_after_time(&self.map, (plan.moves.len() + dist) as i64);
+        if plan.moves.len() < 2 || gathered <= defenders + dist as i64 {
+            return None;
+        }
+        self.gather = plan.moves;
+        self.next_gather_move()
+    }
+
+    // A new city pays for itself in `2 * city_cost` turns, so it is only founded
+    // on a safe cell when there is nothing valuable to attack nearby
+    fn find_build(&self, best_score: f64) -> Option<Action> {
+        let rules = &self.map.rules;
+        if !rules.buildings || best_score >= 50.0 {
+            return None;
+        }
+        let payback_turns = 2 * rules.city_cost as u32;
+        if rules.timed_match && rules.time_limit == TimeLimit::Turns && self.map.turn + payback_turns > rules.turn_limit {
+            return None;
+        }
+        let mut enemies = vec![];
+        for y in 0..self.map.n {
+            for x in 0..self.map.m {
+                let cell = self.map.grid[y][x];
+                if cell.owner.is_some() && cell.owner != Some(self.map.curr_color) {
+                    enemies.push((y, x));
+                }
+            }
+        }
+        let mut best_cell = None;
+        let mut best_army = rules.city_cost * 3 / 2;
+        for y in 0..self.map.n {
+            for x in 0..self.map.m {
+                let army = self.map.grid[y][x].army_size;
+                if army <= best_army || !self.map.is_a_valid_build((y, x), Structure::City) {
+                    continue;
+                }
+                if enemies.iter().any(|&enemy| self.map.distance(enemy, (y, x)) <= 5) {
+                    continue;
+                }
+                best_cell = Some((y, x));
+                best_army = army;
+            }
+        }
+        best_cell.map(|coords| Action::Build(coords, Structure::City))
+    }
 }
 
 impl Bot for PathFinderBot {
-    fn get_best_move(&mut self, strength: f64) -> Option<Move> {
+    fn get_best_action(&mut self, strength: f64) -> Option<Action> {
         if fastrand::f64() * 100.0 > strength {
-            return self.get_random_move();
+            return self.get_random_move().map(Action::Move);
+        }
+        if self.map.rules.attrition {
+            self.supply = self.map.supply_distances(self.map.curr_color);
+        }
+        let threats = threat::find_threats(&self.map, self.map.curr_color);
+        if let Some(action) = self.evacuate_general(&threats) {
+            return Some(action);
+        }
+        if let Some(mv) = tactics::tactical_move(&self.map, self.map.curr_color) {
+            self.gather.clear();
+            return Some(Action::Move(mv));
+        }
+        if let Some(mv) = self.defend(&threats) {
+            self.gather.clear();
+            return Some(Action::Move(mv));
+        }
+        if let Some(wall) = self.find_wall(&threats) {
+            return Some(wall);
+        }
+        if let Some(action) = self.opening_action() {
+            return action;
+        }
+        if let Some(mv) = self.next_gather_move() {
+            return Some(Action::Move(mv));
         }
         let mut best_score = -1e9;
         let mut best_move = None;
+        let mut best_priority = -1e9;
         let mut start_cells = vec![];
         for y in 0..self.map.n {
             for x in 0..self.map.m {
@@ -167,9 +666,16 @@ impl Bot for PathFinderBot {
                 if cell.owner != Some(self.map.curr_color) || cell.army_size <= 1 || cell.last_update_time != self.map.turn {
                     continue;
                 }
+                // Threatened generals and cities keep their garrison
+                if threats.iter().any(|threat| threat.target == (y, x)) {
+                    continue;
+                }
                 let mut priority = cell.army_size;
                 if cell.cell_type == CellType::General && cell.owner == Some(self.map.curr_color) {
-                    priority = ((priority as f64 - 10.0) * 0.5) as i64;
+                    priority = ((priority as f64 - self.personality.general_garrison()) * 0.5) as i64;
+                }
+                if self.map.objective.flag == Some((y, x)) {
+                    priority = i64::MAX / 2;
                 }
                 start_cells.push((-priority, fastrand::u32(0..=u32::MAX), y, x));
             }
@@ -179,37 +685,40 @@ impl Bot for PathFinderBot {
             let grid = self.find_paths(&self.map, (y, x));
             for y1 in 0..self.map.n {
                 for x1 in 0..self.map.m {
-                    let priority = self.eval_target_cell(&self.map, (y1, x1));
+                    let priority = self
+                        .eval_target_cell(&self.map, (y1, x1))
+                        .max(self.eval_objective(&self.map, (y, x), (y1, x1)));
                     let info = grid[y1][x1];
                     if info.value < 1 {
                         continue;
                     };
                     let score = priority / info.dist as f64;
                     if score > best_score {
-                        let mut curr_coords = info.coords;
-                        if curr_coords == (y, x) || info.parent.0 == usize::MAX {
+                        let Some(new_move) = Self::first_step(&grid, (y, x), info.coords) else {
                             continue;
-                        }
-                        let new_move = loop {
-                            let prev_coords = grid[curr_coords.0][curr_coords.1].parent;
-                            if prev_coords == (y, x) {
-                                let (y2, x2) = curr_coords;
-                                let curr_move = Move::new(y, x, y2, x2);
-                                break curr_move;
-                            }
-                            curr_coords = prev_coords;
                         };
                         assert!(self.map.is_a_valid_move(new_move));
                         best_move = Some(new_move);
+                        best_priority = priority;
                         best_score = score;
                     }
                 }
             }
         }
-        best_move
+        // Nothing valuable is in reach, so the army is gathered for a bigger target first
+        if best_priority < GATHER_PRIORITY {
+            if let Some(mv) = self.start_gather() {
+                return Some(Action::Move(mv));
+            }
+        }
+        if let Some(build) = self.find_build(best_score) {
+            return Some(build);
+        }
+        best_move.map(Action::Move)
     }
     
-    fn update_from_map(&mut self, map: &GameMap) {
-        self.map.update_from(map);
+    fn update_from_view(&mut self, view: &PlayerView) {
+        self.map = view.map.clone();
+        self.belief.update(view);
     }
 }
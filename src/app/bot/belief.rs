@@ -0,0 +1,223 @@
+use std::collections::VecDeque;
+
+use crate::app::map::cell::CellType;
+use crate::app::map::view::PlayerView;
+use crate::app::map::GameMap;
+
+// Every observed move of an enemy stack shifts the odds towards the side it came from by this factor
+const MOVE_EVIDENCE: f64 = 1.5;
+const MAX_MOVE_EVIDENCE: f64 = 3.0;
+// Generals usually sit inside their land, far cells lose this much log weight per step
+const LAND_ATTRACTION: f64 = 0.15;
+
+// Probability map over the location of every enemy general, built only from what a player has seen
+#[derive(Clone, Default)]
+pub struct GeneralBelief {
+    player_id: usize,
+    // Cells that were seen without a general on them
+    ruled_out: Vec<Vec<bool>>,
+    // The first turn every cell was seen owned by an enemy, indexed by player id
+    first_seen: Vec<Vec<Vec<Option<u32>>>>,
+    // Cells close enough to every sighting of a player for a single general to have captured all of them
+    reachable: Vec<Vec<Vec<bool>>>,
+    // Log weights collected from observed moves, indexed by player id
+    move_evidence: Vec<Vec<Vec<f64>>>,
+    probabilities: Vec<Vec<Vec<f64>>>,
+    previous: Option<PlayerView>,
+}
+
+impl GeneralBelief {
+    pub fn new(view: &PlayerView) -> GeneralBelief {
+        let map = &view.map;
+        let mut belief = GeneralBelief {
+            player_id: view.player_id,
+            ruled_out: vec![vec![false; map.m]; map.n],
+            first_seen: vec![vec![vec![None; map.m]; map.n]; map.players_num],
+            reachable: vec![vec![vec![true; map.m]; map.n]; map.players_num],
+            move_evidence: vec![vec![vec![0.0; map.m]; map.n]; map.players_num],
+            probabilities: vec![vec![vec![0.0; map.m]; map.n]; map.players_num],
+            previous: None,
+        };
+        belief.update(view);
+        belief
+    }
+
+    pub fn update(&mut self, view: &PlayerView) {
+        let map = &view.map;
+        // A mobile general may walk into a cell that was empty before
+        if map.rules.mobile_generals {
+            self.ruled_out = vec![vec![false; map.m]; map.n];
+        }
+        for y in 0..map.n {
+            for x in 0..map.m {
+                if !view.is_visible(y, x) {
+                    continue;
+                }
+                let cell = map.grid[y][x];
+                if cell.cell_type != CellType::General {
+                    self.ruled_out[y][x] = true;
+                }
+                if let Some(id) = cell.owner {
+                    if id != self.player_id && self.first_seen[id][y][x].is_none() {
+                        self.first_seen[id][y][x] = Some(view.turn);
+                        self.restrict(map, id, (y, x), view.turn);
+                    }
+                }
+            }
+        }
+        self.record_moves(view);
+        for id in 0..map.players_num {
+            self.probabilities[id] = self.estimate(view, id);
+        }
+        self.previous = Some(view.clone());
+    }
+
+    // Land seen at turn `t` is at most `t` moves away from the general it was captured from
+    fn restrict(&mut self, map: &GameMap, id: usize, sighting: (usize, usize), turn: u32) {
+        // Late sightings don't rule anything out
+        if turn as usize + 1 >= map.n + map.m {
+            return;
+        }
+        for (y, row) in self.reachable[id].iter_mut().enumerate() {
+            for (x, reachable) in row.iter_mut().enumerate() {
+                if map.distance((y, x), sighting) > turn as usize + 1 {
+                    *reachable = false;
+                }
+            }
+        }
+    }
+
+    // Stacks that moved between two visible cells since the last turn point back to their general
+    fn record_moves(&mut self, view: &PlayerView) {
+        let Some(previous) = &self.previous else {
+            return;
+        };
+        let map = &view.map;
+        let seen_twice = |y: usize, x: usize| view.is_visible(y, x) && previous.is_visible(y, x);
+        for y in 0..map.n {
+            for x in 0..map.m {
+                let (before, now) = (previous.map.grid[y][x], map.grid[y][x]);
+                let Some(id) = before.owner else {
+                    continue;
+                };
+                if id == self.player_id || !seen_twice(y, x) || before.army_size < 3 || now.owner != Some(id) || now.army_size != 1 {
+                    continue;
+                }
+                let target = map.neighbours(y, x).find(|&(ny, nx)| {
+                    seen_twice(ny, nx)
+                        && map.grid[ny][nx].owner == Some(id)
+                        && (previous.map.grid[ny][nx].owner != Some(id) || map.grid[ny][nx].army_size > previous.map.grid[ny][nx].army_size)
+                });
+                let Some(target) = target else {
+                    continue;
+                };
+                for (ey, row) in self.move_evidence[id].iter_mut().enumerate() {
+                    for (ex, weight) in row.iter_mut().enumerate() {
+                        let (from_dist, to_dist) = (map.distance((ey, ex), (y, x)), map.distance((ey, ex), target));
+                        let delta = match from_dist.cmp(&to_dist) {
+                            std::cmp::Ordering::Less => MOVE_EVIDENCE.ln(),
+                            std::cmp::Ordering::Greater => -MOVE_EVIDENCE.ln(),
+                            std::cmp::Ordering::Equal => 0.0,
+                        };
+                        *weight = (*weight + delta).clamp(-MAX_MOVE_EVIDENCE, MAX_MOVE_EVIDENCE);
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_alive(view: &PlayerView, id: usize) -> bool {
+        match &view.leaderboard {
+            Some(leaderboard) => leaderboard[id].total_fields > 0,
+            None => true,
+        }
+    }
+
+    // Distance from every cell to the closest cell seen owned by the player
+    fn land_distances(&self, map: &GameMap, id: usize) -> Vec<Vec<usize>> {
+        let mut dist = vec![vec![usize::MAX; map.m]; map.n];
+        let mut queue = VecDeque::new();
+        for (y, row) in self.first_seen[id].iter().enumerate() {
+            for (x, first_seen) in row.iter().enumerate() {
+                if first_seen.is_some() {
+                    dist[y][x] = 0;
+                    queue.push_back((y, x));
+                }
+            }
+        }
+        while let Some((y, x)) = queue.pop_front() {
+            for (ny, nx) in map.neighbours(y, x) {
+                if dist[ny][nx] == usize::MAX {
+                    dist[ny][nx] = dist[y][x] + 1;
+                    queue.push_back((ny, nx));
+                }
+            }
+        }
+        dist
+    }
+
+    fn estimate(&self, view: &PlayerView, id: usize) -> Vec<Vec<f64>> {
+        let map = &view.map;
+        let mut result = vec![vec![0.0; map.m]; map.n];
+        if id == self.player_id || !Self::is_alive(view, id) {
+            return result;
+        }
+        let known = Self::known_generals(view, id);
+        if known.len() < map.rules.capitals.max(1) {
+            let land_distances = self.land_distances(map, id);
+            // Several capitals and land inherited from beaten players don't grow from a single general
+            let single_origin = !map.rules.mobile_generals && map.rules.capitals <= 1 && map.generals_captured[id] == 0;
+            let mut total = self.weigh(view, id, &land_distances, single_origin, &mut result);
+            if total == 0.0 && single_origin {
+                total = self.weigh(view, id, &land_distances, false, &mut result);
+            }
+            if total > 0.0 {
+                result.iter_mut().flatten().for_each(|probability| *probability /= total);
+            }
+        }
+        for (y, x) in known {
+            result[y][x] = 1.0;
+        }
+        result
+    }
+
+    // Fills in the unnormalized weights of the cells that may hold a hidden general and returns their sum
+    fn weigh(&self, view: &PlayerView, id: usize, land_distances: &[Vec<usize>], bounded: bool, result: &mut [Vec<f64>]) -> f64 {
+        let map = &view.map;
+        let mut total = 0.0;
+        for (y, row) in result.iter_mut().enumerate() {
+            for (x, probability) in row.iter_mut().enumerate() {
+                let cell = map.grid[y][x];
+                *probability = 0.0;
+                if self.ruled_out[y][x] || cell.cell_type == CellType::Mountains || cell.owner.is_some_and(|owner| owner != id) {
+                    continue;
+                }
+                if bounded && !self.reachable[id][y][x] {
+                    continue;
+                }
+                let land_distance = match land_distances[y][x] {
+                    usize::MAX => 0,
+                    distance => distance,
+                };
+                *probability = (self.move_evidence[id][y][x] - LAND_ATTRACTION * land_distance as f64).exp();
+                total += *probability;
+            }
+        }
+        total
+    }
+
+    // Remembered generals stay in place unless generals are mobile
+    fn known_generals(view: &PlayerView, id: usize) -> Vec<(usize, usize)> {
+        let map = &view.map;
+        (0..map.n)
+            .flat_map(|y| (0..map.m).map(move |x| (y, x)))
+            .filter(|&(y, x)| !map.rules.mobile_generals || view.is_visible(y, x))
+            .filter(|&(y, x)| map.grid[y][x].cell_type == CellType::General && map.grid[y][x].owner == Some(id))
+            .collect()
+    }
+
+    // Chance that there is an enemy general on the cell
+    pub fn enemy_general_probability(&self, y: usize, x: usize) -> f64 {
+        self.probabilities.iter().map(|probabilities| probabilities[y][x]).sum()
+    }
+}
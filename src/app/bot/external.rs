@@ -0,0 +1,248 @@
+// Line based protocol spoken with bots running as separate processes.
+//
+// When the game starts the bot receives
+//     init <rows> <columns> <players> <your id> <square|octile|hex> <wrap: 0|1>
+// Every turn it receives its observation followed by `go`:
+//     turn <turn>
+//     stats <army> <land> <cities>
+//     player <id> <army> <land>    one line per player, omitted in silent war
+//     row <cell> <cell> ...        one line per map row
+//     go <turn>
+// Every cell is written as `<type>:<owner>:<army>:<last seen turn>:<visible>` where type is `E` (empty),
+// `M` (mountain or unknown obstacle), `C` (city) or `G` (general), owner is `-` for neutral cells
+// and visible is 1 for cells seen right now and 0 for the rest. Cells that were never seen were last seen on turn 0.
+// The bot answers with a single line that repeats the turn it was given in `go`:
+//     move <turn> <y1> <x1> <y2> <x2>
+//     general <turn> <y1> <x1> <y2> <x2>
+//     build <turn> <y> <x> <city|wall>
+//     pass <turn>
+// A bot that doesn't answer in time passes its turn and its late answer is ignored.
+// Observations wait in a queue while the bot is busy. A bot that exits, falls 16 observations behind
+// or leaves one unread for 5 seconds passes for the rest of the game.
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::Bot;
+use crate::app::map::cell::CellType;
+use crate::app::map::topology::Topology;
+use crate::app::map::view::PlayerView;
+use crate::app::map::{Action, Move, Structure};
+
+// Programs given by a bare name are looked up in PATH the same way the process is started
+pub fn check_program(program: &str) -> Result<(), String> {
+    let path = Path::new(program);
+    let found = if path.components().count() > 1 {
+        path.is_file()
+    } else {
+        std::env::var_os("PATH").is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+    };
+    if found {
+        Ok(())
+    } else {
+        Err(format!("program {} not found", program))
+    }
+}
+
+const MAX_PENDING_MESSAGES: usize = 16;
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Process {
+    child: Child,
+    // Messages written to the bot's stdin by a separate thread
+    messages: SyncSender<String>,
+    // Start of the write the thread is blocked on
+    writing_since: Arc<Mutex<Option<Instant>>>,
+    lines: Receiver<String>,
+}
+
+pub struct ExternalBot {
+    program: String,
+    // `None` once the process failed to start or crashed
+    process: Option<Process>,
+    view: PlayerView,
+    time_limit: Duration,
+}
+
+impl ExternalBot {
+    pub fn spawn(view: &PlayerView, program: &str, time_limit: Duration) -> Self {
+        let mut bot = Self {
+            program: program.to_string(),
+            process: None,
+            view: view.clone(),
+            time_limit,
+        };
+        match Self::start_process(program) {
+            Ok(process) => bot.process = Some(process),
+            Err(err) => println!("Couldn't start external bot {}: {}", program, err),
+        }
+        let map = &view.map;
+        let topology = match map.rules.topology {
+            Topology::Square => "square",
+            Topology::Octile => "octile",
+            Topology::Hex => "hex",
+        };
+        let wrap = map.rules.wrap_around as u8;
+        bot.send(&format!("init {} {} {} {} {} {}\n", map.n, map.m, map.players_num, view.player_id, topology, wrap));
+        bot
+    }
+
+    fn start_process(program: &str) -> std::io::Result<Process> {
+        let mut child = Command::new(program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        // Writing happens on a separate thread too, a bot that stops reading would block the game on a full pipe
+        let (messages, pending) = mpsc::sync_channel::<String>(MAX_PENDING_MESSAGES);
+        let writing_since = Arc::new(Mutex::new(None));
+        let writer_state = Arc::clone(&writing_since);
+        thread::spawn(move || {
+            for message in pending {
+                *writer_state.lock().unwrap_or_else(|err| err.into_inner()) = Some(Instant::now());
+                if stdin.write_all(message.as_bytes()).and_then(|_| stdin.flush()).is_err() {
+                    break;
+                }
+                *writer_state.lock().unwrap_or_else(|err| err.into_inner()) = None;
+            }
+        });
+        // Reading happens on a separate thread so that a silent bot can't block the game
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Process { child, messages, writing_since, lines })
+    }
+
+    fn send(&mut self, message: &str) {
+        let Some(process) = &self.process else {
+            return;
+        };
+        let writing_since = *process.writing_since.lock().unwrap_or_else(|err| err.into_inner());
+        if writing_since.is_some_and(|since| since.elapsed() > WRITE_TIMEOUT) {
+            println!("External bot {} stopped reading its input", self.program);
+            self.crash();
+            return;
+        }
+        match process.messages.try_send(message.to_string()) {
+            Ok(()) => {},
+            Err(TrySendError::Full(_)) => {
+                println!("External bot {} fell too far behind", self.program);
+                self.crash();
+            },
+            Err(TrySendError::Disconnected(_)) => self.crash(),
+        }
+    }
+
+    fn crash(&mut self) {
+        println!("External bot {} crashed, it will pass for the rest of the game", self.program);
+        if let Some(mut process) = self.process.take() {
+            let _ = process.child.kill();
+            let _ = process.child.wait();
+        }
+    }
+
+    fn encode_view(&self) -> String {
+        let view = &self.view;
+        let mut message = format!("turn {}\n", view.turn);
+        message += &format!("stats {} {} {}\n", view.stats.total_army, view.stats.total_fields, view.stats.total_cities);
+        if let Some(leaderboard) = &view.leaderboard {
+            for (id, stats) in leaderboard.iter().enumerate() {
+                message += &format!("player {} {} {}\n", id, stats.total_army, stats.total_fields);
+            }
+        }
+        for (y, row) in view.map.grid.iter().enumerate() {
+            message += "row";
+            for (x, cell) in row.iter().enumerate() {
+                let cell_type = match cell.cell_type {
+                    CellType::Empty => 'E',
+                    CellType::Mountains => 'M',
+                    CellType::City => 'C',
+                    CellType::General => 'G',
+                };
+                let owner = cell.owner.map_or("-".to_string(), |id| id.to_string());
+                let visible = view.is_visible(y, x) as u8;
+                message += &format!(" {}:{}:{}:{}:{}", cell_type, owner, cell.army_size, cell.last_update_time, visible);
+            }
+            message += "\n";
+        }
+        message += &format!("go {}\n", view.turn);
+        message
+    }
+}
+
+// The turn the answer was given for and the action, `None` for unknown commands
+fn parse_answer(line: &str) -> Option<(u32, Option<Action>)> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let turn = words.get(1)?.parse().ok()?;
+    let numbers: Vec<usize> = words.iter().skip(2).map_while(|word| word.parse().ok()).collect();
+    let action = match (words[0], numbers.as_slice()) {
+        ("pass", &[]) => None,
+        ("move", &[y1, x1, y2, x2]) => Some(Action::Move(Move::new(y1, x1, y2, x2))),
+        ("general", &[y1, x1, y2, x2]) => Some(Action::MoveGeneral(Move::new(y1, x1, y2, x2))),
+        ("build", &[y, x]) => match words.get(4).copied()? {
+            "city" => Some(Action::Build((y, x), Structure::City)),
+            "wall" => Some(Action::Build((y, x), Structure::Wall)),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some((turn, action))
+}
+
+impl Bot for ExternalBot {
+    fn get_best_action(&mut self, _strength: f64) -> Option<Action> {
+        let message = self.encode_view();
+        self.send(&message);
+        let deadline = Instant::now() + self.time_limit;
+        loop {
+            let process = self.process.as_ref()?;
+            match process.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) => match parse_answer(&line) {
+                    Some((turn, action)) if turn == self.view.turn => return action,
+                    // Late answers to previous turns are dropped
+                    Some(_) => {},
+                    None => {
+                        println!("External bot {} sent an unknown command: {}", self.program, line);
+                        return None;
+                    },
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    println!("External bot {} ran out of time", self.program);
+                    return None;
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.crash();
+                    return None;
+                },
+            }
+        }
+    }
+
+    fn update_from_view(&mut self, view: &PlayerView) {
+        self.view = view.clone();
+    }
+}
+
+impl Drop for ExternalBot {
+    fn drop(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            let _ = process.child.kill();
+            let _ = process.child.wait();
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+
+use crate::app::map::cell::CellType;
+use crate::app::map::{GameMap, Move};
+
+// Moves that pull scattered armies of a player into one cell, leaves of the gather tree move first
+pub struct GatherPlan {
+    pub moves: VecDeque<Move>,
+    // Army that arrives at the root on top of what is already there
+    pub army: i64,
+}
+
+struct Node {
+    coords: (usize, usize),
+    parent: usize,
+    depth: usize,
+    included: bool,
+}
+
+// Tree of own visible cells rooted at `root`, every cell is reached by the shortest path.
+// Generals keep their garrison and are left out
+fn build_tree(map: &GameMap, me: usize, root: (usize, usize)) -> Vec<Node> {
+    let mut index = vec![vec![usize::MAX; map.m]; map.n];
+    let mut tree = vec![Node { coords: root, parent: usize::MAX, depth: 0, included: true }];
+    index[root.0][root.1] = 0;
+    let mut queue = VecDeque::from([0]);
+    while let Some(node) = queue.pop_front() {
+        let (y, x) = tree[node].coords;
+        for (ny, nx) in map.neighbours(y, x) {
+            let cell = map.grid[ny][nx];
+            if index[ny][nx] != usize::MAX || cell.owner != Some(me) || cell.last_update_time != map.turn {
+                continue;
+            }
+            if cell.cell_type == CellType::General || cell.cell_type == CellType::Mountains {
+                continue;
+            }
+            index[ny][nx] = tree.len();
+            tree.push(Node { coords: (ny, nx), parent: node, depth: tree[node].depth + 1, included: false });
+            queue.push_back(tree.len() - 1);
+        }
+    }
+    tree
+}
+
+// Army collected and moves needed to attach the branch ending at `node` to the included part of the tree
+fn branch_value(map: &GameMap, tree: &[Node], mut node: usize) -> (i64, usize) {
+    let (mut army, mut moves) = (0, 0);
+    while !tree[node].included {
+        let (y, x) = tree[node].coords;
+        army += map.grid[y][x].army_size - 1;
+        moves += 1;
+        node = tree[node].parent;
+    }
+    (army, moves)
+}
+
+// Greedily grows the gathered subtree by the branch that brings the most army per move
+pub fn plan_gather(map: &GameMap, me: usize, root: (usize, usize), max_moves: usize) -> GatherPlan {
+    let mut tree = build_tree(map, me, root);
+    let mut moves_left = max_moves;
+    let mut army = 0;
+    loop {
+        let best = (1..tree.len())
+            .filter(|&node| !tree[node].included && map.grid[tree[node].coords.0][tree[node].coords.1].army_size > 1)
+            .map(|node| (node, branch_value(map, &tree, node)))
+            .filter(|&(_, (_, moves))| moves <= moves_left)
+            .max_by(|&(_, (army_a, moves_a)), &(_, (army_b, moves_b))| {
+                (army_a as f64 / moves_a as f64).total_cmp(&(army_b as f64 / moves_b as f64))
+            });
+        let Some((mut node, (branch_army, branch_moves))) = best else {
+            break;
+        };
+        army += branch_army;
+        moves_left -= branch_moves;
+        while !tree[node].included {
+            tree[node].included = true;
+            node = tree[node].parent;
+        }
+    }
+    let mut nodes: Vec<&Node> = tree.iter().skip(1).filter(|node| node.included).collect();
+    nodes.sort_by_key(|node| std::cmp::Reverse(node.depth));
+    let moves = nodes
+        .into_iter()
+        .map(|node| {
+            let (y, x) = node.coords;
+            let (py, px) = tree[node.parent].coords;
+            Move::new(y, x, py, px)
+        })
+        .collect();
+    GatherPlan { moves, army }
+}
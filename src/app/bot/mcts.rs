@@ -0,0 +1,246 @@
+use egui_macroquad::macroquad::time::get_time;
+
+use super::Bot;
+use crate::app::map::cell::CellType;
+use crate::app::map::view::PlayerView;
+use crate::app::map::{Action, GameMap};
+
+// Own turns played by the default policy after leaving the tree
+const ROLLOUT_TURNS: u32 = 8;
+// Only moves from the biggest stacks are considered
+const SOURCE_CELLS: usize = 6;
+const EXPLORATION: f64 = 1.4;
+// Searches run inside the game tick, longer ones would freeze the window
+pub const MAX_SEARCH_MS: u64 = 250;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum SearchBudget {
+    TimeMs(u64),
+    Iterations(u32),
+}
+
+struct Node {
+    // `None` stands for passing the turn
+    action: Option<Action>,
+    children: Vec<usize>,
+    untried: Vec<Option<Action>>,
+    visits: u32,
+    total_reward: f64,
+}
+
+impl Node {
+    fn new(action: Option<Action>, untried: Vec<Option<Action>>) -> Node {
+        Node {
+            action,
+            children: vec![],
+            untried,
+            visits: 0,
+            total_reward: 0.0,
+        }
+    }
+}
+
+// Open loop Monte Carlo tree search over own moves, opponents follow the rollout policy.
+// Every iteration runs on a fresh determinization of the fogged map
+pub struct MctsBot {
+    view: PlayerView,
+    budget: SearchBudget,
+}
+
+impl MctsBot {
+    pub fn from_view(view: &PlayerView, budget: SearchBudget) -> Self {
+        Self {
+            view: view.clone(),
+            budget,
+        }
+    }
+
+    fn me(&self) -> usize {
+        self.view.player_id
+    }
+
+    // Fills the fog with a guess: every enemy without a known general gets one on a random hidden cell
+    fn determinize(&self) -> GameMap {
+        let mut map = self.view.map.clone();
+        let hidden: Vec<(usize, usize)> = (0..map.n)
+            .flat_map(|y| (0..map.m).map(move |x| (y, x)))
+            .filter(|&(y, x)| !self.view.is_visible(y, x) && map.grid[y][x].cell_type == CellType::Empty)
+            .collect();
+        for id in (0..map.players_num).filter(|&id| id != self.me()) {
+            let alive = match &self.view.leaderboard {
+                Some(leaderboard) => leaderboard[id].total_fields > 0,
+                None => true,
+            };
+            if !alive || map.is_alive(id) {
+                continue;
+            }
+            let Some(&(y, x)) = fastrand::choice(&hidden) else {
+                continue;
+            };
+            let cell = &mut map.grid[y][x];
+            cell.cell_type = CellType::General;
+            cell.owner = Some(id);
+            cell.army_size = 1 + map.turn as i64 / 4;
+        }
+        Self::refresh(&mut map);
+        map
+    }
+
+    // Moves are only valid from cells seen on the current turn, everything in the simulation counts as seen
+    fn refresh(map: &mut GameMap) {
+        let turn = map.turn;
+        map.grid.iter_mut().flatten().for_each(|cell| cell.last_update_time = turn);
+    }
+
+    // Moves of the biggest stacks of the current player and a pass
+    fn candidate_actions(map: &GameMap) -> Vec<Option<Action>> {
+        let mut moves = map.get_all_moves();
+        let mut sources: Vec<(i64, (usize, usize))> = moves.iter().map(|mv| (map.grid[mv.from.0][mv.from.1].army_size, mv.from)).collect();
+        sources.sort_unstable_by(|a, b| b.cmp(a));
+        sources.dedup();
+        sources.truncate(SOURCE_CELLS);
+        moves.retain(|mv| sources.iter().any(|&(_, from)| from == mv.from));
+        let mut actions: Vec<Option<Action>> = moves.into_iter().map(|mv| Some(Action::Move(mv))).collect();
+        actions.push(None);
+        actions
+    }
+
+    // Cheap default policy: the biggest stacks prefer capturing cells they can take
+    fn rollout_action(map: &GameMap) -> Option<Action> {
+        let moves = map.get_all_moves();
+        let captures: Vec<_> = moves
+            .iter()
+            .copied()
+            .filter(|mv| {
+                let (from, to) = (map.grid[mv.from.0][mv.from.1], map.grid[mv.to.0][mv.to.1]);
+                to.owner != from.owner && from.army_size - 1 > to.army_size
+            })
+            .collect();
+        let pool = if !captures.is_empty() && fastrand::f64() < 0.8 { captures } else { moves };
+        fastrand::choice(&pool).copied().map(Action::Move)
+    }
+
+    fn play(map: &mut GameMap, action: Option<Action>) {
+        let turn = map.turn;
+        match action {
+            Some(action) if map.is_a_valid_action(action) => map.make_action(action),
+            _ => map.skip_turn(),
+        }
+        if map.turn != turn {
+            Self::refresh(map);
+        }
+    }
+
+    // Lets the other players act until it's our turn again
+    fn play_opponents(&self, map: &mut GameMap) {
+        while map.curr_color != self.me() && map.winner.is_none() {
+            Self::play(map, Self::rollout_action(map));
+        }
+    }
+
+    fn evaluate(&self, map: &GameMap) -> f64 {
+        match map.winner {
+            Some(id) if id == self.me() => return 1.0,
+            Some(_) => return 0.0,
+            None => {},
+        }
+        if !map.is_alive(self.me()) {
+            return 0.0;
+        }
+        let stats = map.get_statistics();
+        let strength = |id: usize| stats[id].total_fields as f64 + stats[id].total_army as f64 * 0.5 + stats[id].total_cities as f64 * 10.0;
+        let total: f64 = (0..map.players_num).map(strength).sum();
+        if total <= 0.0 {
+            return 0.5;
+        }
+        strength(self.me()) / total
+    }
+
+    fn select_child(&self, tree: &[Node], node: usize) -> usize {
+        let parent_visits = tree[node].visits.max(1) as f64;
+        *tree[node]
+            .children
+            .iter()
+            .max_by(|&&a, &&b| {
+                let ucb = |child: usize| {
+                    let child = &tree[child];
+                    let visits = child.visits.max(1) as f64;
+                    child.total_reward / visits + EXPLORATION * (parent_visits.ln() / visits).sqrt()
+                };
+                ucb(a).total_cmp(&ucb(b))
+            })
+            .expect("Node has children")
+    }
+
+    fn iterate(&self, tree: &mut Vec<Node>) {
+        let mut map = self.determinize();
+        let mut path = vec![0];
+        let mut node = 0;
+        loop {
+            if map.winner.is_some() {
+                break;
+            }
+            if !tree[node].untried.is_empty() {
+                let index = fastrand::usize(0..tree[node].untried.len());
+                let action = tree[node].untried.swap_remove(index);
+                Self::play(&mut map, action);
+                self.play_opponents(&mut map);
+                tree.push(Node::new(action, Self::candidate_actions(&map)));
+                let child = tree.len() - 1;
+                tree[node].children.push(child);
+                path.push(child);
+                break;
+            }
+            if tree[node].children.is_empty() {
+                break;
+            }
+            node = self.select_child(tree, node);
+            Self::play(&mut map, tree[node].action);
+            self.play_opponents(&mut map);
+            path.push(node);
+        }
+        for _ in 0..ROLLOUT_TURNS {
+            if map.winner.is_some() {
+                break;
+            }
+            let action = Self::rollout_action(&map);
+            Self::play(&mut map, action);
+            self.play_opponents(&mut map);
+        }
+        let reward = self.evaluate(&map);
+        for &node in &path {
+            tree[node].visits += 1;
+            tree[node].total_reward += reward;
+        }
+    }
+}
+
+impl Bot for MctsBot {
+    fn get_best_action(&mut self, strength: f64) -> Option<Action> {
+        if fastrand::f64() * 100.0 > strength {
+            return Self::rollout_action(&self.view.map);
+        }
+        let mut tree = vec![Node::new(None, Self::candidate_actions(&self.view.map))];
+        let start_time = get_time();
+        let mut iterations = 0;
+        loop {
+            let elapsed_ms = (get_time() - start_time) * 1000.0;
+            let done = elapsed_ms >= MAX_SEARCH_MS as f64
+                || match self.budget {
+                    SearchBudget::TimeMs(ms) => elapsed_ms >= ms as f64,
+                    SearchBudget::Iterations(limit) => iterations >= limit,
+                };
+            if done {
+                break;
+            }
+            self.iterate(&mut tree);
+            iterations += 1;
+        }
+        let best = tree[0].children.iter().max_by_key(|&&child| tree[child].visits)?;
+        tree[*best].action
+    }
+
+    fn update_from_view(&mut self, view: &PlayerView) {
+        self.view = view.clone();
+    }
+}
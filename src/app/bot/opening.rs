@@ -0,0 +1,231 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+use crate::app::map::cell::CellType;
+use crate::app::map::topology::Topology;
+use crate::app::map::{GameMap, Move};
+
+// Turns covered by the opening plan
+pub const OPENING_TURNS: u32 = 25;
+// Cells further from the general are not used in the opening
+const RADIUS: i64 = 8;
+// Army the general waits for before the first and the following launches
+const FIRST_LAUNCHES: std::ops::RangeInclusive<i64> = 2..=14;
+const NEXT_LAUNCHES: std::ops::RangeInclusive<i64> = 2..=10;
+
+// Everything the best opening depends on: cells around the general as -1 for obstacles,
+// 0 for neutral land and the army of own cells, and the moment the plan starts
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct TerrainKey {
+    topology: Topology,
+    odd_row: bool,
+    turn: u32,
+    growth: i64,
+    cells: Vec<i64>,
+}
+
+// Moves as offsets from the general, one entry per turn, `None` waits for the general to grow
+type Strategy = Vec<Option<((i64, i64), (i64, i64))>>;
+
+// Strategies found so far, games with the same surroundings of a general reuse them
+fn opening_book() -> &'static Mutex<HashMap<TerrainKey, Strategy>> {
+    static BOOK: OnceLock<Mutex<HashMap<TerrainKey, Strategy>>> = OnceLock::new();
+    BOOK.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub struct OpeningPlan {
+    start_turn: u32,
+    steps: Vec<Option<Move>>,
+}
+
+impl OpeningPlan {
+    // `None` after the plan is over, `Some(None)` when the plan waits on this turn
+    pub fn step(&self, turn: u32) -> Option<Option<Move>> {
+        let index = turn.checked_sub(self.start_turn)? as usize;
+        self.steps.get(index).copied()
+    }
+}
+
+// Cells around the general with their offsets, neighbours and values in the same order as in `TerrainKey`
+struct Window {
+    offsets: Vec<(i64, i64)>,
+    neighbours: Vec<Vec<usize>>,
+    cells: Vec<i64>,
+    general: usize,
+}
+
+impl Window {
+    fn new(map: &GameMap, me: usize, general: (usize, usize)) -> Window {
+        let offsets: Vec<(i64, i64)> = (-RADIUS..=RADIUS).flat_map(|dy| (-RADIUS..=RADIUS).map(move |dx| (dy, dx))).collect();
+        let mut coords = vec![];
+        let mut index = HashMap::new();
+        for &(dy, dx) in &offsets {
+            let cell = map
+                .normalize((general.0 as i64 + dy, general.1 as i64 + dx))
+                .filter(|&cell| map.distance(cell, general) <= RADIUS as usize && !index.contains_key(&cell));
+            if let Some(cell) = cell {
+                index.insert(cell, coords.len());
+            }
+            coords.push(cell);
+        }
+        let cells = coords
+            .iter()
+            .map(|&cell| {
+                let Some((y, x)) = cell else {
+                    return -1;
+                };
+                let cell = map.grid[y][x];
+                match cell.owner {
+                    Some(id) if id == me => cell.army_size,
+                    None if cell.cell_type == CellType::Empty && !map.is_hostile(y, x) => 0,
+                    _ => -1,
+                }
+            })
+            .collect();
+        let neighbours = coords
+            .iter()
+            .map(|&cell| match cell {
+                Some((y, x)) => map.neighbours(y, x).filter_map(|coords| index.get(&coords).copied()).collect(),
+                None => vec![],
+            })
+            .collect();
+        Window {
+            general: index[&general],
+            offsets,
+            neighbours,
+            cells,
+        }
+    }
+}
+
+// Route of the next launch: through own land to a frontier cell and then outwards through neutral cells
+fn best_route(window: &Window, cells: &[i64], turns_left: usize) -> Vec<(usize, usize)> {
+    let general = window.general;
+    let stack = cells[general] - 1;
+    let mut parent = vec![usize::MAX; cells.len()];
+    let mut dist = vec![usize::MAX; cells.len()];
+    dist[general] = 0;
+    let mut queue = VecDeque::from([general]);
+    while let Some(i) = queue.pop_front() {
+        for &j in &window.neighbours[i] {
+            if cells[j] > 0 && dist[j] == usize::MAX {
+                dist[j] = dist[i] + 1;
+                parent[j] = i;
+                queue.push_back(j);
+            }
+        }
+    }
+    let mut best: Option<(f64, Vec<(usize, usize)>)> = None;
+    for own in (0..cells.len()).filter(|&i| dist[i] != usize::MAX) {
+        for &frontier in window.neighbours[own].iter().filter(|&&j| cells[j] == 0) {
+            let mut route = vec![];
+            let mut i = own;
+            while i != general {
+                route.push((parent[i], i));
+                i = parent[i];
+            }
+            route.reverse();
+            route.push((own, frontier));
+            // Neutral cells are taken greedily, the ones leading away from the general first
+            let mut visited = vec![frontier];
+            let mut last = frontier;
+            while (visited.len() as i64) < stack && route.len() < turns_left {
+                let next = window.neighbours[last]
+                    .iter()
+                    .copied()
+                    .filter(|&j| cells[j] == 0 && !visited.contains(&j))
+                    .max_by_key(|&j| {
+                        let free = window.neighbours[j].iter().filter(|&&k| cells[k] == 0 && !visited.contains(&k)).count();
+                        (window.offsets[j].0.abs().max(window.offsets[j].1.abs()), free)
+                    });
+                let Some(next) = next else {
+                    break;
+                };
+                route.push((last, next));
+                visited.push(next);
+                last = next;
+            }
+            route.truncate(turns_left);
+            let captured = route.iter().filter(|&&(_, to)| cells[to] == 0).count().min(stack.max(0) as usize);
+            let value = captured as f64 / route.len() as f64;
+            if best.as_ref().is_none_or(|(best_value, _)| value > *best_value) {
+                best = Some((value, route));
+            }
+        }
+    }
+    best.map_or(vec![], |(_, route)| route)
+}
+
+// Plays the opening inside the window launching from the general whenever it reaches the given armies
+fn simulate(window: &Window, key: &TerrainKey, first_launch: i64, next_launch: i64) -> (i64, Vec<Option<(usize, usize)>>) {
+    let mut cells = window.cells.clone();
+    let mut route = VecDeque::new();
+    let mut launched = false;
+    let mut steps = vec![];
+    for turn in key.turn..OPENING_TURNS {
+        let threshold = if launched { next_launch } else { first_launch };
+        if route.is_empty() && cells[window.general] >= threshold {
+            route = best_route(window, &cells, (OPENING_TURNS - turn) as usize).into();
+            launched = true;
+        }
+        let step = route.pop_front().filter(|&(from, _)| cells[from] > 1);
+        if let Some((from, to)) = step {
+            let army = cells[from] - 1;
+            cells[from] = 1;
+            cells[to] += army;
+        } else {
+            route.clear();
+        }
+        steps.push(step);
+        let next_turn = turn + 1;
+        for (i, army) in cells.iter_mut().enumerate() {
+            if *army > 0 && (next_turn % 25 == 0 || i == window.general && next_turn % 2 == 0) {
+                *army += key.growth;
+            }
+        }
+    }
+    let land = cells.iter().filter(|&&army| army > 0).count() as i64;
+    (land * 1000 + cells[window.general], steps)
+}
+
+fn find_strategy(window: &Window, key: &TerrainKey) -> Strategy {
+    let mut best = (i64::MIN, vec![]);
+    for first_launch in FIRST_LAUNCHES {
+        for next_launch in NEXT_LAUNCHES {
+            let result = simulate(window, key, first_launch, next_launch);
+            if result.0 > best.0 {
+                best = result;
+            }
+        }
+    }
+    best.1
+        .into_iter()
+        .map(|step| step.map(|(from, to)| (window.offsets[from], window.offsets[to])))
+        .collect()
+}
+
+// Plan that captures as much land as possible in the first turns, looked up in the opening book first
+pub fn plan_opening(map: &GameMap, me: usize, general: (usize, usize)) -> OpeningPlan {
+    let window = Window::new(map, me, general);
+    let key = TerrainKey {
+        topology: map.rules.topology,
+        odd_row: map.rules.topology == Topology::Hex && general.0 % 2 == 1,
+        turn: map.turn,
+        growth: map.rules.handicap(me).growth_multiplier,
+        cells: window.cells.clone(),
+    };
+    let strategy = {
+        let mut book = opening_book().lock().unwrap_or_else(|err| err.into_inner());
+        book.entry(key.clone()).or_insert_with(|| find_strategy(&window, &key)).clone()
+    };
+    let absolute = |(dy, dx): (i64, i64)| map.normalize((general.0 as i64 + dy, general.1 as i64 + dx));
+    let steps = strategy
+        .into_iter()
+        .map(|step| {
+            let (from, to) = step?;
+            let ((y1, x1), (y2, x2)) = (absolute(from)?, absolute(to)?);
+            Some(Move::new(y1, x1, y2, x2))
+        })
+        .collect();
+    OpeningPlan { start_turn: map.turn, steps }
+}
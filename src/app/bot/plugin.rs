@@ -0,0 +1,162 @@
+// Bots compiled to WebAssembly and run inside a sandbox with limited memory and fuel.
+//
+// A module exports `take_turn()` and may export `init()`, both without parameters and results.
+// It can import these functions from the `env` module:
+//     rows() -> i32, columns() -> i32, players() -> i32, player_id() -> i32, turn() -> i32
+//     cell_type(y, x) -> i32        0 empty, 1 mountain or unknown obstacle, 2 city, 3 general
+//     cell_owner(y, x) -> i32       -1 for neutral cells
+//     cell_army(y, x) -> i64
+//     cell_last_seen(y, x) -> i32   0 for cells that were never seen
+//     cell_visible(y, x) -> i32     1 for cells seen right now, 0 otherwise
+//     submit_move(y1, x1, y2, x2)
+//     submit_general_move(y1, x1, y2, x2)
+//     submit_build(y, x, structure)  0 city, 1 wall
+// The last action submitted during `take_turn` is played, a module that runs out of fuel passes its turn.
+
+use wasmi::core::TrapCode;
+use wasmi::{Caller, Config, Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+use super::Bot;
+use crate::app::map::cell::{CellType, GameCell};
+use crate::app::map::view::PlayerView;
+use crate::app::map::{Action, Move, Structure};
+
+const MAX_MEMORY: usize = 64 << 20;
+const FUEL_PER_TURN: u64 = 50_000_000;
+
+// Loads the module without running it, for reporting errors before a game starts
+pub fn check_module(program: &str) -> Result<(), String> {
+    let wasm = std::fs::read(program).map_err(|err| err.to_string())?;
+    let module = Module::new(&Engine::default(), &wasm).map_err(|err| err.to_string())?;
+    if !module.exports().any(|export| export.name() == "take_turn") {
+        return Err("the module doesn't export `take_turn`".to_string());
+    }
+    Ok(())
+}
+
+struct HostState {
+    view: PlayerView,
+    action: Option<Action>,
+    limits: StoreLimits,
+}
+
+impl HostState {
+    fn cell(&self, y: i32, x: i32) -> Option<GameCell> {
+        let map = &self.view.map;
+        if y < 0 || x < 0 || y as usize >= map.n || x as usize >= map.m {
+            return None;
+        }
+        Some(map.grid[y as usize][x as usize])
+    }
+}
+
+pub struct WasmBot {
+    program: String,
+    // `None` when the module couldn't be loaded
+    instance: Option<(Store<HostState>, TypedFunc<(), ()>)>,
+}
+
+impl WasmBot {
+    pub fn load(view: &PlayerView, program: &str) -> Self {
+        let instance = match Self::instantiate(view, program) {
+            Ok(instance) => Some(instance),
+            Err(err) => {
+                println!("Couldn't load wasm bot {}: {}", program, err);
+                None
+            },
+        };
+        Self {
+            program: program.to_string(),
+            instance,
+        }
+    }
+
+    fn instantiate(view: &PlayerView, program: &str) -> Result<(Store<HostState>, TypedFunc<(), ()>), String> {
+        let wasm = std::fs::read(program).map_err(|err| err.to_string())?;
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, &wasm).map_err(|err| err.to_string())?;
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).instances(1).build();
+        let mut store = Store::new(&engine, HostState { view: view.clone(), action: None, limits });
+        store.limiter(|state| &mut state.limits);
+        let linker = Self::host_api(&engine).map_err(|err| err.to_string())?;
+        store.set_fuel(FUEL_PER_TURN).map_err(|err| err.to_string())?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|err| err.to_string())?;
+        if let Ok(init) = instance.get_typed_func::<(), ()>(&store, "init") {
+            init.call(&mut store, ()).map_err(|err| err.to_string())?;
+        }
+        let take_turn = instance.get_typed_func::<(), ()>(&store, "take_turn").map_err(|err| err.to_string())?;
+        Ok((store, take_turn))
+    }
+
+    fn host_api(engine: &Engine) -> Result<Linker<HostState>, wasmi::errors::LinkerError> {
+        let mut linker = Linker::new(engine);
+        linker
+            .func_wrap("env", "rows", |caller: Caller<HostState>| caller.data().view.map.n as i32)?
+            .func_wrap("env", "columns", |caller: Caller<HostState>| caller.data().view.map.m as i32)?
+            .func_wrap("env", "players", |caller: Caller<HostState>| caller.data().view.map.players_num as i32)?
+            .func_wrap("env", "player_id", |caller: Caller<HostState>| caller.data().view.player_id as i32)?
+            .func_wrap("env", "turn", |caller: Caller<HostState>| caller.data().view.turn as i32)?
+            .func_wrap("env", "cell_type", |caller: Caller<HostState>, y: i32, x: i32| {
+                caller.data().cell(y, x).map_or(1, |cell| match cell.cell_type {
+                    CellType::Empty => 0,
+                    CellType::Mountains => 1,
+                    CellType::City => 2,
+                    CellType::General => 3,
+                })
+            })?
+            .func_wrap("env", "cell_owner", |caller: Caller<HostState>, y: i32, x: i32| {
+                caller.data().cell(y, x).and_then(|cell| cell.owner).map_or(-1, |id| id as i32)
+            })?
+            .func_wrap("env", "cell_army", |caller: Caller<HostState>, y: i32, x: i32| {
+                caller.data().cell(y, x).map_or(0, |cell| cell.army_size)
+            })?
+            .func_wrap("env", "cell_last_seen", |caller: Caller<HostState>, y: i32, x: i32| {
+                caller.data().cell(y, x).map_or(0, |cell| cell.last_update_time as i32)
+            })?
+            .func_wrap("env", "cell_visible", |caller: Caller<HostState>, y: i32, x: i32| {
+                let state = caller.data();
+                (state.cell(y, x).is_some() && state.view.is_visible(y as usize, x as usize)) as i32
+            })?
+            .func_wrap("env", "submit_move", |mut caller: Caller<HostState>, y1: u32, x1: u32, y2: u32, x2: u32| {
+                let mv = Move::new(y1 as usize, x1 as usize, y2 as usize, x2 as usize);
+                caller.data_mut().action = Some(Action::Move(mv));
+            })?
+            .func_wrap("env", "submit_general_move", |mut caller: Caller<HostState>, y1: u32, x1: u32, y2: u32, x2: u32| {
+                let mv = Move::new(y1 as usize, x1 as usize, y2 as usize, x2 as usize);
+                caller.data_mut().action = Some(Action::MoveGeneral(mv));
+            })?
+            .func_wrap("env", "submit_build", |mut caller: Caller<HostState>, y: u32, x: u32, structure: i32| {
+                let structure = if structure == 0 { Structure::City } else { Structure::Wall };
+                caller.data_mut().action = Some(Action::Build((y as usize, x as usize), structure));
+            })?;
+        Ok(linker)
+    }
+}
+
+impl Bot for WasmBot {
+    fn get_best_action(&mut self, _strength: f64) -> Option<Action> {
+        let (store, take_turn) = self.instance.as_mut()?;
+        store.data_mut().action = None;
+        store.set_fuel(FUEL_PER_TURN).ok()?;
+        if let Err(err) = take_turn.call(&mut *store, ()) {
+            if err.as_trap_code() == Some(TrapCode::OutOfFuel) {
+                println!("Wasm bot {} ran out of fuel", self.program);
+            } else {
+                println!("Wasm bot {} failed: {}", self.program, err);
+            }
+            return None;
+        }
+        store.data().action
+    }
+
+    fn update_from_view(&mut self, view: &PlayerView) {
+        if let Some((store, _)) = &mut self.instance {
+            store.data_mut().view = view.clone();
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use super::Bot;
+use crate::app::map::{view::PlayerView, Action, GameMap};
+
+// Makes a random valid move every turn, useful as a sparring partner for beginners
+pub struct RandomBot {
+    map: GameMap,
+}
+
+impl RandomBot {
+    pub fn from_view(view: &PlayerView) -> Self {
+        Self {
+            map: view.map.clone(),
+        }
+    }
+}
+
+impl Bot for RandomBot {
+    fn get_best_action(&mut self, _strength: f64) -> Option<Action> {
+        let moves = self.map.get_all_moves();
+        fastrand::choice(&moves).copied().map(Action::Move)
+    }
+
+    fn update_from_view(&mut self, view: &PlayerView) {
+        self.map = view.map.clone();
+    }
+}
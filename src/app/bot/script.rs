@@ -0,0 +1,199 @@
+// Bots written in Rhai and loaded from a script file when a game starts.
+//
+// A script defines `fn take_turn(view)` and returns `[y1, x1, y2, x2]` to move or `()` to pass.
+// The view exposes `rows`, `columns`, `me` and `turn` together with these methods:
+//     cell_type(y, x)          "empty", "mountain", "city" or "general", unknown obstacles are mountains
+//     owner(y, x)              player id or -1 for neutral cells
+//     army(y, x), visible(y, x), last_seen(y, x)
+//     neighbours(y, x)         array of [y, x]
+//     distances(y, x)          BFS distances around mountains, -1 for unreachable cells
+//     my_cells()               own cells that have an army to move
+//     is_valid_move(y1, x1, y2, x2)
+
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use rhai::{Array, Dynamic, Engine, Scope, AST};
+
+use super::Bot;
+use crate::app::map::cell::CellType;
+use crate::app::map::view::PlayerView;
+use crate::app::map::{Action, Move};
+
+// Protects the game from endless loops in scripts
+const MAX_OPERATIONS: u64 = 5_000_000;
+
+#[derive(Clone)]
+struct ScriptView(Rc<PlayerView>);
+
+impl ScriptView {
+    fn cell_coords(&self, y: i64, x: i64) -> Option<(usize, usize)> {
+        let map = &self.0.map;
+        if y < 0 || x < 0 || y as usize >= map.n || x as usize >= map.m {
+            return None;
+        }
+        Some((y as usize, x as usize))
+    }
+
+    fn cell_type(&mut self, y: i64, x: i64) -> String {
+        let Some((y, x)) = self.cell_coords(y, x) else {
+            return "mountain".to_string();
+        };
+        match self.0.map.grid[y][x].cell_type {
+            CellType::Empty => "empty",
+            CellType::Mountains => "mountain",
+            CellType::City => "city",
+            CellType::General => "general",
+        }
+        .to_string()
+    }
+
+    fn owner(&mut self, y: i64, x: i64) -> i64 {
+        self.cell_coords(y, x)
+            .and_then(|(y, x)| self.0.map.grid[y][x].owner)
+            .map_or(-1, |id| id as i64)
+    }
+
+    fn army(&mut self, y: i64, x: i64) -> i64 {
+        self.cell_coords(y, x).map_or(0, |(y, x)| self.0.map.grid[y][x].army_size)
+    }
+
+    fn visible(&mut self, y: i64, x: i64) -> bool {
+        self.cell_coords(y, x).is_some_and(|(y, x)| self.0.is_visible(y, x))
+    }
+
+    fn last_seen(&mut self, y: i64, x: i64) -> i64 {
+        self.cell_coords(y, x).map_or(0, |(y, x)| self.0.map.grid[y][x].last_update_time as i64)
+    }
+
+    fn neighbours(&mut self, y: i64, x: i64) -> Array {
+        let Some((y, x)) = self.cell_coords(y, x) else {
+            return Array::new();
+        };
+        self.0.map.neighbours(y, x).map(|(ny, nx)| Dynamic::from(vec![Dynamic::from(ny as i64), Dynamic::from(nx as i64)])).collect()
+    }
+
+    fn distances(&mut self, y: i64, x: i64) -> Array {
+        let map = &self.0.map;
+        let mut dist = vec![vec![-1i64; map.m]; map.n];
+        if let Some((y, x)) = self.cell_coords(y, x) {
+            dist[y][x] = 0;
+            let mut queue = VecDeque::from([(y, x)]);
+            while let Some((y, x)) = queue.pop_front() {
+                for (ny, nx) in map.neighbours(y, x) {
+                    if dist[ny][nx] == -1 && map.grid[ny][nx].cell_type != CellType::Mountains {
+                        dist[ny][nx] = dist[y][x] + 1;
+                        queue.push_back((ny, nx));
+                    }
+                }
+            }
+        }
+        dist.into_iter().map(|row| Dynamic::from(row.into_iter().map(Dynamic::from).collect::<Array>())).collect()
+    }
+
+    fn my_cells(&mut self) -> Array {
+        let map = &self.0.map;
+        let mut cells = Array::new();
+        for (y, row) in map.grid.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if cell.owner == Some(self.0.player_id) && cell.army_size > 1 {
+                    cells.push(Dynamic::from(vec![Dynamic::from(y as i64), Dynamic::from(x as i64)]));
+                }
+            }
+        }
+        cells
+    }
+
+    fn is_valid_move(&mut self, y1: i64, x1: i64, y2: i64, x2: i64) -> bool {
+        match (self.cell_coords(y1, x1), self.cell_coords(y2, x2)) {
+            (Some((y1, x1)), Some((y2, x2))) => self.0.map.is_a_valid_move(Move::new(y1, x1, y2, x2)),
+            _ => false,
+        }
+    }
+}
+
+fn create_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine
+        .register_type_with_name::<ScriptView>("View")
+        .register_get("rows", |view: &mut ScriptView| view.0.map.n as i64)
+        .register_get("columns", |view: &mut ScriptView| view.0.map.m as i64)
+        .register_get("me", |view: &mut ScriptView| view.0.player_id as i64)
+        .register_get("turn", |view: &mut ScriptView| view.0.turn as i64)
+        .register_fn("cell_type", ScriptView::cell_type)
+        .register_fn("owner", ScriptView::owner)
+        .register_fn("army", ScriptView::army)
+        .register_fn("visible", ScriptView::visible)
+        .register_fn("last_seen", ScriptView::last_seen)
+        .register_fn("neighbours", ScriptView::neighbours)
+        .register_fn("distances", ScriptView::distances)
+        .register_fn("my_cells", ScriptView::my_cells)
+        .register_fn("is_valid_move", ScriptView::is_valid_move);
+    engine
+}
+
+// Compiles the script without running it, used to report errors in the menu
+pub fn check_script(program: &str) -> Result<(), String> {
+    create_engine().compile_file(PathBuf::from(program)).map(|_| ()).map_err(|err| err.to_string())
+}
+
+pub struct ScriptBot {
+    program: String,
+    engine: Engine,
+    // `None` when the script failed to compile
+    ast: Option<AST>,
+    view: Rc<PlayerView>,
+}
+
+impl ScriptBot {
+    pub fn load(view: &PlayerView, program: &str) -> Self {
+        let engine = create_engine();
+        let ast = match engine.compile_file(PathBuf::from(program)) {
+            Ok(ast) => Some(ast),
+            Err(err) => {
+                println!("Couldn't load script bot {}: {}", program, err);
+                None
+            },
+        };
+        Self {
+            program: program.to_string(),
+            engine,
+            ast,
+            view: Rc::new(view.clone()),
+        }
+    }
+}
+
+impl Bot for ScriptBot {
+    fn get_best_action(&mut self, _strength: f64) -> Option<Action> {
+        let ast = self.ast.as_ref()?;
+        let view = ScriptView(self.view.clone());
+        let result = self.engine.call_fn::<Dynamic>(&mut Scope::new(), ast, "take_turn", (view,));
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                println!("Script bot {} failed: {}", self.program, err);
+                return None;
+            },
+        };
+        if result.is_unit() {
+            return None;
+        }
+        let coords: Option<Vec<usize>> = result
+            .try_cast::<Array>()
+            .and_then(|array| array.into_iter().map(|value| value.as_int().ok().map(|value| value as usize)).collect());
+        match coords.as_deref() {
+            Some(&[y1, x1, y2, x2]) => Some(Action::Move(Move::new(y1, x1, y2, x2))),
+            _ => {
+                println!("Script bot {} returned something that is not a move", self.program);
+                None
+            },
+        }
+    }
+
+    fn update_from_view(&mut self, view: &PlayerView) {
+        self.view = Rc::new(view.clone());
+    }
+}
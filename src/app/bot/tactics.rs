@@ -0,0 +1,164 @@
+use crate::app::map::cell::{CellType, GameCell};
+use crate::app::map::{GameMap, Move};
+
+// An enemy stack this close to our general or next to our army starts a fight
+const GENERAL_CONTACT_DISTANCE: usize = 3;
+const REGION_RADIUS: usize = 3;
+// Plies of the search, both sides move on every turn
+const DEPTH: u32 = 4;
+// Only the biggest stacks of each side are moved in the search
+const MAX_MOVERS: usize = 5;
+const GENERAL_VALUE: f64 = 1e6;
+
+// Cells around a fight with their neighbours inside the region
+#[derive(Clone)]
+struct Region {
+    coords: Vec<(usize, usize)>,
+    neighbours: Vec<Vec<usize>>,
+}
+
+#[derive(Clone)]
+struct Position {
+    cells: Vec<GameCell>,
+    // Owner of a general that was captured during the search
+    lost_general: Option<usize>,
+}
+
+// Finds the enemy stack that is about to fight us
+fn find_contact(map: &GameMap, me: usize) -> Option<((usize, usize), usize)> {
+    let mut generals = vec![];
+    let mut stacks = vec![];
+    let mut enemies = vec![];
+    for (y, row) in map.grid.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if cell.last_update_time != map.turn {
+                continue;
+            }
+            match cell.owner {
+                Some(id) if id == me && cell.cell_type == CellType::General => generals.push((y, x)),
+                Some(id) if id == me && cell.army_size > 2 => stacks.push((y, x)),
+                Some(id) if id != me && cell.army_size > 2 => enemies.push(((y, x), id)),
+                _ => {},
+            }
+        }
+    }
+    enemies
+        .into_iter()
+        .filter(|&(enemy, _)| {
+            generals.iter().any(|&general| map.distance(general, enemy) <= GENERAL_CONTACT_DISTANCE)
+                || stacks.iter().any(|&stack| map.distance(stack, enemy) <= 1)
+        })
+        .max_by_key(|&((y, x), _)| map.grid[y][x].army_size)
+}
+
+fn build_region(map: &GameMap, center: (usize, usize)) -> Region {
+    let coords: Vec<(usize, usize)> = (0..map.n)
+        .flat_map(|y| (0..map.m).map(move |x| (y, x)))
+        .filter(|&(y, x)| map.grid[y][x].cell_type != CellType::Mountains && map.distance((y, x), center) <= REGION_RADIUS)
+        .collect();
+    let neighbours = coords
+        .iter()
+        .map(|&(y, x)| {
+            map.neighbours(y, x)
+                .filter_map(|coords_to| coords.iter().position(|&c| c == coords_to))
+                .collect()
+        })
+        .collect();
+    Region { coords, neighbours }
+}
+
+impl Position {
+    fn moves(&self, region: &Region, player: usize) -> Vec<(usize, usize)> {
+        let mut movers: Vec<usize> = (0..self.cells.len())
+            .filter(|&i| self.cells[i].owner == Some(player) && self.cells[i].army_size > 1)
+            .collect();
+        movers.sort_by_key(|&i| -self.cells[i].army_size);
+        movers.truncate(MAX_MOVERS);
+        let mut moves: Vec<(usize, usize)> = movers
+            .into_iter()
+            .flat_map(|from| region.neighbours[from].iter().map(move |&to| (from, to)))
+            .collect();
+        // Captures first to get more cut-offs
+        moves.sort_by_key(|&(from, to)| self.cells[to].owner == Some(player) || self.cells[from].army_size - 1 <= self.cells[to].army_size);
+        moves
+    }
+
+    fn play(&self, player: usize, (from, to): (usize, usize)) -> Position {
+        let mut next = self.clone();
+        let army = next.cells[from].army_size - 1;
+        next.cells[from].army_size = 1;
+        let target = &mut next.cells[to];
+        if target.owner == Some(player) {
+            target.army_size += army;
+        } else if army > target.army_size {
+            if target.cell_type == CellType::General {
+                next.lost_general = target.owner;
+                target.cell_type = CellType::City;
+            }
+            target.army_size = army - target.army_size;
+            target.owner = Some(player);
+        } else {
+            target.army_size -= army;
+        }
+        next
+    }
+
+    fn evaluate(&self, me: usize) -> f64 {
+        match self.lost_general {
+            Some(id) if id == me => return -GENERAL_VALUE,
+            Some(_) => return GENERAL_VALUE,
+            None => {},
+        }
+        self.cells
+            .iter()
+            .map(|cell| match cell.owner {
+                Some(id) if id == me => cell.army_size as f64 + 0.5,
+                Some(_) => -(cell.army_size as f64 + 0.5),
+                None => 0.0,
+            })
+            .sum()
+    }
+}
+
+fn alpha_beta(region: &Region, position: &Position, players: [usize; 2], depth: u32, mut alpha: f64, beta: f64) -> f64 {
+    let [player, opponent] = players;
+    if depth == 0 || position.lost_general.is_some() {
+        return position.evaluate(player);
+    }
+    // Passing is always allowed
+    let mut best = -alpha_beta(region, position, [opponent, player], depth - 1, -beta, -alpha);
+    alpha = alpha.max(best);
+    for mv in position.moves(region, player) {
+        if alpha >= beta {
+            break;
+        }
+        let next = position.play(player, mv);
+        let score = -alpha_beta(region, &next, [opponent, player], depth - 1, -beta, -alpha);
+        best = best.max(score);
+        alpha = alpha.max(score);
+    }
+    best
+}
+
+// Best move in an imminent local fight, `None` when there is no fight or passing is as good as any move
+pub fn tactical_move(map: &GameMap, me: usize) -> Option<Move> {
+    let (center, enemy) = find_contact(map, me)?;
+    let region = build_region(map, center);
+    let position = Position {
+        cells: region.coords.iter().map(|&(y, x)| map.grid[y][x]).collect(),
+        lost_general: None,
+    };
+    let pass_score = -alpha_beta(&region, &position, [enemy, me], DEPTH - 1, f64::NEG_INFINITY, f64::INFINITY);
+    let mut best_score = pass_score;
+    let mut best_move = None;
+    for mv in position.moves(&region, me) {
+        let next = position.play(me, mv);
+        let score = -alpha_beta(&region, &next, [enemy, me], DEPTH - 1, f64::NEG_INFINITY, -best_score);
+        if score > best_score {
+            best_score = score;
+            best_move = Some(mv);
+        }
+    }
+    let ((y1, x1), (y2, x2)) = best_move.map(|(from, to)| (region.coords[from], region.coords[to]))?;
+    Some(Move::new(y1, x1, y2, x2))
+}
@@ -0,0 +1,80 @@
+use std::collections::VecDeque;
+
+use crate::app::map::cell::CellType;
+use crate::app::map::GameMap;
+
+// Enemy stacks further away than this are not considered dangerous yet
+const THREAT_HORIZON: usize = 10;
+
+#[derive(Clone, Copy)]
+pub struct Threat {
+    // Own general or city the attacker can capture
+    pub target: (usize, usize),
+    pub attacker: (usize, usize),
+    // Moves the attacker needs to reach the target
+    pub turns: usize,
+    // Army the attacker keeps after capturing the target when nobody comes to help
+    pub excess: i64,
+}
+
+impl Threat {
+    pub fn is_general(&self, map: &GameMap) -> bool {
+        map.grid[self.target.0][self.target.1].cell_type == CellType::General
+    }
+}
+
+// Number of moves from every cell to `target` around mountains
+fn distances_to(map: &GameMap, target: (usize, usize)) -> Vec<Vec<usize>> {
+    let mut dist = vec![vec![usize::MAX; map.m]; map.n];
+    dist[target.0][target.1] = 0;
+    let mut queue = VecDeque::from([target]);
+    while let Some((y, x)) = queue.pop_front() {
+        if dist[y][x] >= THREAT_HORIZON {
+            continue;
+        }
+        for (ny, nx) in map.neighbours(y, x) {
+            if dist[ny][nx] == usize::MAX && map.grid[ny][nx].cell_type != CellType::Mountains {
+                dist[ny][nx] = dist[y][x] + 1;
+                queue.push_back((ny, nx));
+            }
+        }
+    }
+    dist
+}
+
+// Visible enemy stacks that would capture one of our generals or cities, the most urgent ones first.
+// Attackers leave a unit on every cell they pass and defenders grow every second turn
+pub fn find_threats(map: &GameMap, me: usize) -> Vec<Threat> {
+    let mut targets = vec![];
+    let mut enemies = vec![];
+    for (y, row) in map.grid.iter().enumerate() {
+        for (x, cell) in row.iter().enumerate() {
+            if cell.last_update_time != map.turn {
+                continue;
+            }
+            match cell.owner {
+                Some(id) if id == me && cell.city_or_general() => targets.push((y, x)),
+                Some(id) if id != me && cell.army_size > 1 => enemies.push((y, x)),
+                _ => {},
+            }
+        }
+    }
+    let mut threats = vec![];
+    for &target in &targets {
+        let dist = distances_to(map, target);
+        let defenders = map.grid[target.0][target.1].army_size;
+        let worst = enemies
+            .iter()
+            .filter(|&&(y, x)| dist[y][x] != usize::MAX)
+            .map(|&(y, x)| {
+                let turns = dist[y][x];
+                let excess = map.grid[y][x].army_size - turns as i64 - (defenders + turns as i64 / 2);
+                Threat { target, attacker: (y, x), turns, excess }
+            })
+            .filter(|threat| threat.excess > 0)
+            .min_by_key(|threat| (threat.turns, -threat.excess));
+        threats.extend(worst);
+    }
+    threats.sort_by_key(|threat| (!threat.is_general(map), threat.turns));
+    threats
+}
@@ -1,8 +1,15 @@
 pub mod cell;
+pub mod objective;
+pub mod rules;
+pub mod topology;
+pub mod view;
 
 use cell::{CellType, GameCell};
+use objective::ObjectiveState;
+use rules::{GameRules, Objective, TimeLimit};
+use topology::Board;
 
-use crate::constants::DIRECTIONS;
+use std::collections::VecDeque;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Move {
@@ -19,10 +26,25 @@ impl Move {
     }
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Structure {
+    City,
+    Wall,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Action {
+    Move(Move),
+    Build((usize, usize), Structure),
+    MoveGeneral(Move),
+}
+
 #[derive(Default, Clone, Eq, PartialEq)]
 pub struct PlayerStatistics {
     pub total_army: i32,
     pub total_fields: i32,
+    pub total_cities: i32,
+    pub generals_captured: i32,
 }
 
 #[derive(Default, Clone)]
@@ -33,27 +55,42 @@ pub struct GameMap {
     pub curr_color: usize,
     pub turn: u32,
     pub grid: Vec<Vec<GameCell>>,
+    pub rules: GameRules,
+    pub objective: ObjectiveState,
+    pub winner: Option<usize>,
+    pub generals_captured: Vec<i32>,
+    // Expiration turn and position of every raised wall
+    pub walls: Vec<(u32, (usize, usize))>,
+    pub barbarian_camps: Vec<(usize, usize)>,
+    // Positions of roaming neutral armies
+    pub barbarians: Vec<(usize, usize)>,
 }
 
 impl GameMap {
-    pub fn new_random(n: usize, m: usize, k: usize) -> GameMap {
+    pub fn new_random(n: usize, m: usize, k: usize, rules: GameRules) -> GameMap {
         let mut grid = vec![vec![GameCell::default(); m]; n];
+        let board = Board { topology: rules.topology, n, m, wrap_around: rules.wrap_around };
         for y in 0..n {
             for x in 0..m {
                 if fastrand::f32() < 0.15 {
                     grid[y][x].cell_type = CellType::Mountains;
-                    if !Self::is_connected(n, m, &grid) {
+                    // Any mountain can be razed, so the map doesn't have to stay connected
+                    if !rules.destructible_mountains && !Self::is_connected(board, &grid) {
                         grid[y][x].cell_type = CellType::Empty;
                     }
                 }
             }
         }
-        for id in 0..k {
+        let objective_cell = match rules.objective {
+            Objective::Conquest => None,
+            Objective::KingOfTheHill | Objective::CaptureTheFlag => Self::closest_open_cell(board, (n / 2, m / 2), &grid),
+        };
+        for id in (0..k).flat_map(|id| std::iter::repeat_n(id, rules.capitals.max(1))) {
             let mut iters = 0;
             loop {
                 iters += 1;
                 let (y, x) = (fastrand::usize(0..n), fastrand::usize(0..m));
-                let dist = Self::dist_to_general(n, m, x, y, &grid);
+                let dist = Self::dist_to_general(board, x, y, &grid);
                 if dist <= 3 && iters < 500 {
                     continue;
                 }
@@ -61,25 +98,50 @@ impl GameMap {
                     println!("WARNING: couldn't find a good position!");
                 }
                 let cell = &mut grid[y][x];
-                if cell.is_empty_not_owned() {
+                if cell.is_empty_not_owned() && Some((y, x)) != objective_cell {
                     cell.owner = Some(id);
                     cell.cell_type = CellType::General;
-                    cell.army_size = 1;
+                    cell.army_size = 1 + rules.handicap(id).starting_army;
                     break;
                 }
             }
         }
         for y in 0..n {
             for x in 0..m {
-                if fastrand::f32() < 0.05 && grid[y][x].is_empty_not_owned() {
+                if fastrand::f32() < 0.05 && grid[y][x].is_empty_not_owned() && Some((y, x)) != objective_cell {
                     grid[y][x].cell_type = CellType::City;
                     grid[y][x].army_size = fastrand::i64(20..=50);
-                    if !Self::is_connected(n, m, &grid) {
+                    if !rules.destructible_mountains && !Self::is_connected(board, &grid) {
                         grid[y][x].cell_type = CellType::Empty;
                     }
                 }
             }
         }
+        let mut barbarian_camps = vec![];
+        if rules.barbarians {
+            for _ in 0..rules.barbarian_camps {
+                for _ in 0..500 {
+                    let (y, x) = (fastrand::usize(0..n), fastrand::usize(0..m));
+                    if grid[y][x].is_empty_not_owned() && Some((y, x)) != objective_cell && Self::dist_to_general(board, x, y, &grid) > 5 {
+                        grid[y][x].cell_type = CellType::City;
+                        grid[y][x].army_size = fastrand::i64(20..=40);
+                        barbarian_camps.push((y, x));
+                        break;
+                    }
+                }
+            }
+        }
+        let mut objective = ObjectiveState::default();
+        match rules.objective {
+            Objective::Conquest => {},
+            Objective::KingOfTheHill => objective.hill = objective_cell,
+            Objective::CaptureTheFlag => {
+                objective.flag = objective_cell;
+                if let Some((y, x)) = objective_cell {
+                    grid[y][x].army_size = GameRules::FLAG_GARRISON;
+                }
+            }
+        }
         GameMap {
             n,
             m,
@@ -87,22 +149,37 @@ impl GameMap {
             curr_color: 0,
             grid,
             turn: 0,
+            rules,
+            objective,
+            winner: None,
+            generals_captured: vec![0; k],
+            walls: vec![],
+            barbarian_camps,
+            barbarians: vec![],
         }
     }
 
-    fn dist_to_general(n: usize, m: usize, x: usize, y: usize, grid: &Vec<Vec<GameCell>>) -> usize {
+    fn closest_open_cell(board: Board, target: (usize, usize), grid: &[Vec<GameCell>]) -> Option<(usize, usize)> {
+        (0..board.n)
+            .flat_map(|y| (0..board.m).map(move |x| (y, x)))
+            .filter(|&(y, x)| grid[y][x].is_empty_not_owned())
+            .min_by_key(|&coords| board.distance(coords, target))
+    }
+
+    fn dist_to_general(board: Board, x: usize, y: usize, grid: &Vec<Vec<GameCell>>) -> usize {
         let mut res = usize::MAX;
-        for y1 in 0..n {
-            for x1 in 0..m {
+        for y1 in 0..board.n {
+            for x1 in 0..board.m {
                 if grid[y1][x1].cell_type == CellType::General {
-                    res = res.min(y1.abs_diff(y) + x1.abs_diff(x));
+                    res = res.min(board.distance((y1, x1), (y, x)));
                 }
             }
         }
         res
     }
 
-    fn is_connected(n: usize, m: usize, grid: &Vec<Vec<GameCell>>) -> bool {
+    fn is_connected(board: Board, grid: &Vec<Vec<GameCell>>) -> bool {
+        let (n, m) = (board.n, board.m);
         let mut used = vec![vec![false; m]; n];
         let mut start_cell = (0, 0);
         'a: for y in 0..n {
@@ -118,10 +195,8 @@ impl GameMap {
         while !st.is_empty() {
             let &(y, x) = st.last().unwrap();
             st.pop();
-            for (dy, dx) in DIRECTIONS {
-                let ny = y.wrapping_add(dy);
-                let nx = x.wrapping_add(dx);
-                if ny < n && nx < m && !used[ny][nx] && grid[ny][nx].cell_type != CellType::Mountains {
+            for (ny, nx) in board.neighbours(y, x) {
+                if !used[ny][nx] && grid[ny][nx].cell_type != CellType::Mountains {
                     st.push((ny, nx));
                     used[ny][nx] = true;
                 }
@@ -142,8 +217,204 @@ impl GameMap {
         for y in 0..self.n {
             for x in 0..self.m {
                 let cell = &mut self.grid[y][x];
-                if cell.owner.is_some() && (self.turn % 25 == 0 || cell.city_or_general() && self.turn % 2 == 0) {
-                    cell.army_size += 1;
+                let Some(owner) = cell.owner else {
+                    continue;
+                };
+                if self.turn % 25 == 0 || cell.city_or_general() && self.turn % 2 == 0 {
+                    let growth = if self.rules.city_state && cell.cell_type == CellType::City { 2 } else { 1 };
+                    cell.army_size += growth * self.rules.handicap(owner).growth_multiplier;
+                }
+            }
+        }
+        self.expire_walls();
+        self.apply_attrition();
+        self.move_barbarians();
+        self.apply_zone();
+        self.update_hill();
+        if self.rules.timed_match && self.rules.time_limit == TimeLimit::Turns && self.turn >= self.rules.turn_limit {
+            self.finish_by_score();
+        }
+    }
+
+    pub fn score(&self, stats: &PlayerStatistics) -> f64 {
+        let weights = &self.rules.score_weights;
+        stats.total_fields as f64 * weights.land
+            + stats.total_army as f64 * weights.army
+            + stats.total_cities as f64 * weights.cities
+            + stats.generals_captured as f64 * weights.generals_captured
+    }
+
+    // Players sorted from the best score to the worst one
+    pub fn ranking(&self) -> Vec<(usize, f64)> {
+        let stats = self.get_statistics();
+        let mut ranking: Vec<(usize, f64)> = (0..self.players_num).map(|id| (id, self.score(&stats[id]))).collect();
+        ranking.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranking
+    }
+
+    pub fn finish_by_score(&mut self) {
+        if self.winner.is_none() {
+            self.winner = self.ranking().first().map(|&(id, _)| id);
+        }
+    }
+
+    fn update_hill(&mut self) {
+        let Some((y, x)) = self.objective.hill else {
+            return;
+        };
+        let holder = self.grid[y][x].owner;
+        if holder.is_some() && holder == self.objective.hill_holder {
+            self.objective.hill_held_turns += 1;
+        } else {
+            self.objective.hill_holder = holder;
+            self.objective.hill_held_turns = if holder.is_some() { 1 } else { 0 };
+        }
+        if self.objective.hill_held_turns >= self.rules.hill_hold_turns && self.winner.is_none() {
+            self.winner = holder;
+        }
+    }
+
+    pub fn flag_carrier(&self) -> Option<usize> {
+        let (y, x) = self.objective.flag?;
+        self.grid[y][x].owner
+    }
+
+    fn update_flag(&mut self, game_move: Move) {
+        let Move { from, to } = game_move;
+        if self.objective.flag != Some(from) {
+            return;
+        }
+        // The flag follows the army only if the move succeeded, neutral armies never carry it
+        let owner = self.grid[from.0][from.1].owner;
+        if owner.is_some() && self.grid[to.0][to.1].owner == owner {
+            self.objective.flag = Some(to);
+        }
+        self.check_flag_delivered();
+    }
+
+    // The flag is delivered when it shares a cell with a general, either brought by an army or reached by a general
+    fn check_flag_delivered(&mut self) {
+        let Some((y, x)) = self.objective.flag else {
+            return;
+        };
+        let cell = self.grid[y][x];
+        if cell.cell_type == CellType::General && cell.owner.is_some() && self.winner.is_none() {
+            self.winner = cell.owner;
+        }
+    }
+
+    pub fn is_alive(&self, player_id: usize) -> bool {
+        self.grid
+            .iter()
+            .flatten()
+            .any(|cell| cell.owner == Some(player_id) && cell.cell_type == CellType::General)
+    }
+
+    fn update_conquest_winner(&mut self) {
+        if self.winner.is_some() {
+            return;
+        }
+        let alive: Vec<usize> = (0..self.players_num).filter(|&id| self.is_alive(id)).collect();
+        if alive.len() == 1 {
+            self.winner = Some(alive[0]);
+        }
+    }
+
+    // Path distance from every cell to the closest city or general of the player
+    pub fn supply_distances(&self, player_id: usize) -> Vec<Vec<usize>> {
+        let mut dist = vec![vec![usize::MAX; self.m]; self.n];
+        let mut queue = VecDeque::new();
+        for (y, row) in self.grid.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                if cell.owner == Some(player_id) && cell.city_or_general() {
+                    dist[y][x] = 0;
+                    queue.push_back((y, x));
+                }
+            }
+        }
+        while let Some((y, x)) = queue.pop_front() {
+            for (ny, nx) in self.neighbours(y, x) {
+                if dist[ny][nx] == usize::MAX && self.grid[ny][nx].cell_type != CellType::Mountains {
+                    dist[ny][nx] = dist[y][x] + 1;
+                    queue.push_back((ny, nx));
+                }
+            }
+        }
+        dist
+    }
+
+    pub fn is_out_of_supply(&self, supply: &[Vec<usize>], y: usize, x: usize) -> bool {
+        self.rules.attrition && supply[y][x] > self.rules.supply_range
+    }
+
+    fn apply_attrition(&mut self) {
+        if !self.rules.attrition {
+            return;
+        }
+        for id in 0..self.players_num {
+            let supply = self.supply_distances(id);
+            for y in 0..self.n {
+                for x in 0..self.m {
+                    let out_of_supply = self.is_out_of_supply(&supply, y, x);
+                    let cell = &mut self.grid[y][x];
+                    if cell.owner == Some(id) && cell.army_size >= self.rules.attrition_min_army && out_of_supply {
+                        cell.army_size -= 1;
+                    }
+                }
+            }
+        }
+    }
+
+    // Number of border rings that are outside of the safe zone
+    pub fn hostile_rings(&self) -> usize {
+        if !self.rules.shrinking_zone || self.turn < self.rules.zone_start_turn {
+            return 0;
+        }
+        let rings = 1 + (self.turn - self.rules.zone_start_turn) / self.rules.zone_shrink_interval.max(1);
+        (rings as usize).min((self.n.min(self.m) - 1) / 2)
+    }
+
+    pub fn turns_until_zone_shrinks(&self) -> Option<u32> {
+        if !self.rules.shrinking_zone || self.hostile_rings() == (self.n.min(self.m) - 1) / 2 {
+            return None;
+        }
+        if self.turn < self.rules.zone_start_turn {
+            return Some(self.rules.zone_start_turn - self.turn);
+        }
+        let interval = self.rules.zone_shrink_interval.max(1);
+        Some(interval - (self.turn - self.rules.zone_start_turn) % interval)
+    }
+
+    pub fn ring_of(&self, y: usize, x: usize) -> usize {
+        y.min(x).min(self.n - 1 - y).min(self.m - 1 - x)
+    }
+
+    pub fn is_hostile(&self, y: usize, x: usize) -> bool {
+        self.ring_of(y, x) < self.hostile_rings()
+    }
+
+    // The innermost hostile ring drains armies, the ones outside of it collapse into mountains
+    fn apply_zone(&mut self) {
+        let rings = self.hostile_rings();
+        if rings == 0 {
+            return;
+        }
+        for y in 0..self.n {
+            for x in 0..self.m {
+                let ring = self.ring_of(y, x);
+                let cell = self.grid[y][x];
+                if ring + 1 < rings && cell.cell_type != CellType::Mountains {
+                    if cell.cell_type == CellType::General {
+                        self.lose_general(cell.owner.expect("General must have an owner"), None);
+                    }
+                    self.grid[y][x] = GameCell {
+                        cell_type: CellType::Mountains,
+                        ..GameCell::default()
+                    };
+                } else if ring + 1 == rings && cell.army_size > 0 {
+                    let min_army = if cell.owner.is_some() { 1 } else { 0 };
+                    let cell = &mut self.grid[y][x];
+                    cell.army_size = (cell.army_size - (cell.army_size / 4).max(1)).max(min_army);
                 }
             }
         }
@@ -160,30 +431,279 @@ impl GameMap {
         }
     }
 
+    pub fn board(&self) -> Board {
+        Board {
+            topology: self.rules.topology,
+            n: self.n,
+            m: self.m,
+            wrap_around: self.rules.wrap_around,
+        }
+    }
+
+    pub fn neighbours(&self, y: usize, x: usize) -> impl Iterator<Item = (usize, usize)> {
+        self.board().neighbours(y, x)
+    }
+
+    pub fn are_adjacent(&self, a: (usize, usize), b: (usize, usize)) -> bool {
+        self.neighbours(a.0, a.1).any(|coords| coords == b)
+    }
+
+    pub fn distance(&self, a: (usize, usize), b: (usize, usize)) -> usize {
+        self.board().distance(a, b)
+    }
+
+    pub fn normalize(&self, coords: (i64, i64)) -> Option<(usize, usize)> {
+        self.board().normalize(coords)
+    }
+
+    pub fn generals_count(&self, player_id: usize) -> usize {
+        self.grid
+            .iter()
+            .flatten()
+            .filter(|cell| cell.owner == Some(player_id) && cell.cell_type == CellType::General)
+            .count()
+    }
+
+    // Must be called while the lost general is still on the grid
+    fn lose_general(&mut self, player_id: usize, new_owner: Option<usize>) {
+        if self.generals_count(player_id) <= 1 {
+            self.destroy_player(player_id, new_owner);
+        }
+    }
+
+    pub fn is_a_valid_general_move(&self, game_move: Move) -> bool {
+        let Move { from: (y1, x1), to: (y2, x2) } = game_move;
+        if !self.rules.mobile_generals || !self.could_become_a_valid_move(game_move) {
+            return false;
+        }
+        let from = self.grid[y1][x1];
+        let to = self.grid[y2][x2];
+        from.cell_type == CellType::General
+            && from.owner == Some(self.curr_color)
+            && from.last_update_time == self.turn
+            && to.cell_type == CellType::Empty
+            && to.owner == Some(self.curr_color)
+    }
+
+    pub fn move_general(&mut self, game_move: Move) {
+        let Move { from: (y1, x1), to: (y2, x2) } = game_move;
+        let escort = self.grid[y1][x1].army_size / 2;
+        let from = &mut self.grid[y1][x1];
+        from.cell_type = CellType::Empty;
+        from.army_size -= escort;
+        let to = &mut self.grid[y2][x2];
+        to.cell_type = CellType::General;
+        to.army_size += escort;
+        self.check_flag_delivered();
+        self.skip_turn();
+    }
+
     pub fn make_move(&mut self, game_move: Move) {
+        self.resolve_move(game_move, 1);
+        self.skip_turn();
+    }
+
+    // Moves everything except `garrison` units from one cell to another and resolves the fight
+    fn resolve_move(&mut self, game_move: Move, garrison: i64) {
         let Move { from: (y1, x1), to: (y2, x2) } = game_move;
         let mut cell1 = self.grid[y1][x1];
         let mut cell2 = self.grid[y2][x2];
-        if cell2.owner == cell1.owner {
-            cell2.army_size += cell1.army_size - 1;
+        if cell2.cell_type == CellType::Mountains {
+            cell2 = GameCell {
+                army_size: cell1.army_size - garrison - self.rules.mountain_cost,
+                owner: cell1.owner,
+                last_update_time: cell2.last_update_time,
+                ..GameCell::default()
+            };
+        } else if cell2.owner == cell1.owner {
+            cell2.army_size += cell1.army_size - garrison;
         } else {
-            cell2.army_size -= cell1.army_size - 1;
+            let defenders = cell2.army_size;
+            cell2.army_size -= cell1.army_size - garrison;
             if cell2.army_size < 0 {
                 cell2.army_size *= -1;
                 if cell2.cell_type == CellType::General {
-                    self.destroy_player(cell2.owner.expect("General must have an owner"), cell1.owner);
+                    if self.rules.leapfrog {
+                        cell2.army_size += defenders;
+                    }
+                    self.lose_general(cell2.owner.expect("General must have an owner"), cell1.owner);
+                    if let Some(id) = cell1.owner {
+                        self.generals_captured[id] += 1;
+                    }
                     cell2.cell_type = CellType::City;
                 }
                 cell2.owner = cell1.owner;
             }
         }
-        cell1.army_size = 1;
+        cell1.army_size = garrison;
         self.grid[y1][x1] = cell1;
         self.grid[y2][x2] = cell2;
+        self.update_flag(game_move);
+    }
+
+    fn spawn_barbarians(&mut self) {
+        for i in 0..self.barbarian_camps.len() {
+            let (y, x) = self.barbarian_camps[i];
+            // Captured camps and camps destroyed by the shrinking zone stay silent
+            if self.grid[y][x].owner.is_some() || self.grid[y][x].cell_type != CellType::City {
+                continue;
+            }
+            for (ny, nx) in self.neighbours(y, x) {
+                let is_objective = self.objective.hill == Some((ny, nx)) || self.objective.flag == Some((ny, nx));
+                if self.grid[ny][nx].is_empty_not_owned() && self.grid[ny][nx].army_size == 0 && !is_objective {
+                    self.grid[ny][nx].army_size = self.rules.barbarian_army;
+                    self.barbarians.push((ny, nx));
+                    break;
+                }
+            }
+        }
+    }
+
+    // First step towards the closest player cell within sight, or a random step if there is none
+    fn barbarian_step(&self, start: (usize, usize)) -> Option<(usize, usize)> {
+        let can_enter = |(y, x): (usize, usize)| {
+            let cell = self.grid[y][x];
+            // Barbarians leave the objectives to the players
+            if self.objective.hill == Some((y, x)) || self.objective.flag == Some((y, x)) {
+                return false;
+            }
+            cell.owner.is_some() && cell.cell_type != CellType::Mountains || cell.is_empty_not_owned()
+        };
+        let mut parent = vec![vec![None; self.m]; self.n];
+        let mut dist = vec![vec![usize::MAX; self.m]; self.n];
+        dist[start.0][start.1] = 0;
+        let mut queue = VecDeque::from([start]);
+        while let Some((y, x)) = queue.pop_front() {
+            if self.grid[y][x].owner.is_some() {
+                let mut curr = (y, x);
+                while let Some(prev) = parent[curr.0][curr.1] {
+                    if prev == start {
+                        return Some(curr);
+                    }
+                    curr = prev;
+                }
+            }
+            if dist[y][x] >= GameRules::BARBARIAN_SIGHT {
+                continue;
+            }
+            for (ny, nx) in self.neighbours(y, x) {
+                if dist[ny][nx] == usize::MAX && can_enter((ny, nx)) {
+                    dist[ny][nx] = dist[y][x] + 1;
+                    parent[ny][nx] = Some((y, x));
+                    queue.push_back((ny, nx));
+                }
+            }
+        }
+        let steps: Vec<(usize, usize)> = self.neighbours(start.0, start.1).filter(|&coords| can_enter(coords)).collect();
+        fastrand::choice(steps)
+    }
+
+    fn move_barbarians(&mut self) {
+        if !self.rules.barbarians {
+            return;
+        }
+        if self.turn.is_multiple_of(self.rules.barbarian_spawn_interval.max(1)) {
+            self.spawn_barbarians();
+        }
+        let mut survivors = vec![];
+        for (y, x) in std::mem::take(&mut self.barbarians) {
+            let cell = self.grid[y][x];
+            // The army was destroyed or absorbed by a player
+            if cell.owner.is_some() || cell.army_size <= 0 || survivors.contains(&(y, x)) {
+                continue;
+            }
+            let Some(to) = self.barbarian_step((y, x)) else {
+                survivors.push((y, x));
+                continue;
+            };
+            self.resolve_move(Move { from: (y, x), to }, 0);
+            if self.grid[to.0][to.1].owner.is_none() && !survivors.contains(&to) {
+                survivors.push(to);
+            }
+        }
+        self.barbarians = survivors;
+    }
+
+    pub fn build_cost(&self, structure: Structure) -> i64 {
+        match structure {
+            Structure::City => self.rules.city_cost,
+            Structure::Wall => self.rules.wall_cost,
+        }
+    }
+
+    pub fn is_a_valid_build(&self, (y, x): (usize, usize), structure: Structure) -> bool {
+        if !self.rules.buildings || y >= self.n || x >= self.m {
+            return false;
+        }
+        let cell = self.grid[y][x];
+        cell.cell_type == CellType::Empty
+            && cell.owner == Some(self.curr_color)
+            && cell.army_size > self.build_cost(structure)
+            && cell.last_update_time == self.turn
+            && self.objective.hill != Some((y, x))
+            && self.objective.flag != Some((y, x))
+    }
+
+    // Whatever army is left on a cell after raising a wall is lost
+    pub fn build(&mut self, (y, x): (usize, usize), structure: Structure) {
+        let cost = self.build_cost(structure);
+        let cell = &mut self.grid[y][x];
+        match structure {
+            Structure::City => {
+                cell.cell_type = CellType::City;
+                cell.army_size -= cost;
+            },
+            Structure::Wall => {
+                *cell = GameCell {
+                    cell_type: CellType::Mountains,
+                    ..GameCell::default()
+                };
+                self.walls.push((self.turn + self.rules.wall_duration, (y, x)));
+            },
+        }
         self.skip_turn();
     }
 
+    fn expire_walls(&mut self) {
+        let turn = self.turn;
+        let grid = &mut self.grid;
+        self.walls.retain(|&(expiration, (y, x))| {
+            if expiration > turn {
+                return true;
+            }
+            if grid[y][x].cell_type == CellType::Mountains {
+                grid[y][x] = GameCell::default();
+            }
+            false
+        });
+    }
+
+    // Players with a delayed start sit out the first turns
+    pub fn can_act(&self, player_id: usize) -> bool {
+        self.turn >= self.rules.handicap(player_id).start_turn
+    }
+
+    pub fn is_a_valid_action(&self, action: Action) -> bool {
+        if !self.can_act(self.curr_color) {
+            return false;
+        }
+        match action {
+            Action::Move(game_move) => self.is_a_valid_move(game_move),
+            Action::Build(coords, structure) => self.is_a_valid_build(coords, structure),
+            Action::MoveGeneral(game_move) => self.is_a_valid_general_move(game_move),
+        }
+    }
+
+    pub fn make_action(&mut self, action: Action) {
+        match action {
+            Action::Move(game_move) => self.make_move(game_move),
+            Action::Build(coords, structure) => self.build(coords, structure),
+            Action::MoveGeneral(game_move) => self.move_general(game_move),
+        }
+    }
+
     pub fn skip_turn(&mut self) {
+        self.update_conquest_winner();
         self.curr_color += 1;
         if self.curr_color >= self.players_num {
             self.curr_color = 0;
@@ -196,12 +716,19 @@ impl GameMap {
             from: (y1, x1),
             to: (y2, x2),
         } = m;
-        if y2 >= self.n || x2 >= self.m || y1 >= self.n || x1 >= self.m || x1.abs_diff(x2) + y1.abs_diff(y2) != 1 {
+        if y2 >= self.n || x2 >= self.m || y1 >= self.n || x1 >= self.m || !self.are_adjacent((y1, x1), (y2, x2)) {
             return false;
         }
         let from = self.grid[y1][x1];
         let to = self.grid[y2][x2];
-        from.cell_type != CellType::Mountains && to.cell_type != CellType::Mountains
+        from.cell_type != CellType::Mountains && (to.cell_type != CellType::Mountains || self.rules.destructible_mountains)
+    }
+
+    fn can_enter(&self, from: &GameCell, to: &GameCell) -> bool {
+        if to.cell_type != CellType::Mountains {
+            return true;
+        }
+        self.rules.destructible_mountains && from.army_size - 1 > self.rules.mountain_cost
     }
 
     pub fn is_a_valid_move(&self, m: Move) -> bool {
@@ -209,7 +736,7 @@ impl GameMap {
             from: (y1, x1),
             to: (y2, x2),
         } = m;
-        if y2 >= self.n || x2 >= self.m || y1 >= self.n || x1 >= self.m || x1.abs_diff(x2) + y1.abs_diff(y2) != 1 {
+        if y2 >= self.n || x2 >= self.m || y1 >= self.n || x1 >= self.m || !self.are_adjacent((y1, x1), (y2, x2)) {
             return false;
         }
         let from = self.grid[y1][x1];
@@ -217,7 +744,7 @@ impl GameMap {
         from.army_size > 1
             && from.cell_type != CellType::Mountains
             && from.owner == Some(self.curr_color)
-            && to.cell_type != CellType::Mountains
+            && self.can_enter(&from, &to)
             && from.last_update_time == self.turn
     }
 
@@ -232,14 +759,9 @@ impl GameMap {
                 {
                     continue;
                 }
-                for (dy, dx) in DIRECTIONS {
-                    let ny = y.wrapping_add(dy);
-                    let nx = x.wrapping_add(dx);
-                    if ny >= self.n || nx >= self.m {
-                        continue;
-                    }
+                for (ny, nx) in self.neighbours(y, x) {
                     let to = &self.grid[ny][nx];
-                    if to.cell_type == CellType::Mountains {
+                    if !self.can_enter(from, to) {
                         continue;
                     }
                     all_moves.push(Move::new(y, x, ny, nx));
@@ -250,14 +772,33 @@ impl GameMap {
     }
 
     pub fn is_visible_to(&self, y: usize, x: usize, id: usize) -> bool {
-        for dy in (-1)..=1 {
-            for dx in (-1)..=1 {
-                let ny = (y as i32 + dy) as usize;
-                let nx = (x as i32 + dx) as usize;
-                if ny >= self.n || nx >= self.m {
+        if self.grid[y][x].owner == Some(id) {
+            return true;
+        }
+        let base_radius = (self.rules.vision_radius + self.rules.handicap(id).extra_vision) as i64;
+        let max_radius = if self.rules.watchtower {
+            base_radius + GameRules::WATCHTOWER_BONUS as i64
+        } else {
+            base_radius
+        };
+        let target = (y as i64, x as i64);
+        for dy in -max_radius..=max_radius {
+            for dx in -max_radius..=max_radius {
+                let from = (target.0 + dy, target.1 + dx);
+                let Some((ny, nx)) = self.normalize(from) else {
+                    continue;
+                };
+                let source = self.grid[ny][nx];
+                if source.owner != Some(id) || self.rules.misty_veil && source.army_size <= 1 {
                     continue;
                 }
-                if self.grid[ny][nx].owner == Some(id) {
+                let radius = if self.rules.watchtower && source.cell_type == CellType::City {
+                    max_radius
+                } else {
+                    base_radius
+                };
+                let in_range = self.rules.topology.in_vision_range(self.rules.vision_shape, from, target, radius);
+                if in_range && self.has_line_of_sight(from, target) {
                     return true;
                 }
             }
@@ -265,6 +806,31 @@ impl GameMap {
         false
     }
 
+    // Samples the segment between cell centres and checks every cell it crosses
+    fn has_line_of_sight(&self, from: (i64, i64), to: (i64, i64)) -> bool {
+        if !self.rules.mountains_block_vision {
+            return true;
+        }
+        let topology = self.rules.topology;
+        let (x1, y1) = topology.cell_center(from.0, from.1);
+        let (x2, y2) = topology.cell_center(to.0, to.1);
+        let steps = topology.signed_distance(from, to) * 4;
+        for i in 1..steps {
+            let t = i as f32 / steps as f32;
+            let coords = topology.cell_at(x1 + (x2 - x1) * t, y1 + (y2 - y1) * t);
+            if coords == from || coords == to {
+                continue;
+            }
+            let Some((y, x)) = self.normalize(coords) else {
+                continue;
+            };
+            if self.grid[y][x].cell_type == CellType::Mountains {
+                return false;
+            }
+        }
+        true
+    }
+
     pub fn get_with_fog(&self, y: usize, x: usize, id: usize) -> GameCell {
         let cell = self.grid[y][x];
         if self.is_visible_to(y, x, id) {
@@ -276,7 +842,7 @@ impl GameMap {
                 owner: None,
                 cell_type: CellType::Mountains,
                 is_friend: false,
-                last_update_time: self.turn,
+                last_update_time: 0,
             };
         } else {
             return GameCell {
@@ -284,18 +850,62 @@ impl GameMap {
                 owner: None,
                 cell_type: CellType::Empty,
                 is_friend: false,
-                last_update_time: self.turn,
+                last_update_time: 0,
             };
         }
     }
 
+    pub fn new_memory(&self, color: usize) -> GameMap {
+        let mut memory = self.clone();
+        memory.curr_color = color;
+        memory.grid = vec![vec![GameCell::default(); self.m]; self.n];
+        memory.walls.clear();
+        memory.barbarian_camps.clear();
+        memory.barbarians.clear();
+        memory
+    }
+
+    pub fn get_statistics(&self) -> Vec<PlayerStatistics> {
+        let mut stats = vec![PlayerStatistics::default(); self.players_num];
+        for row in &self.grid {
+            for cell in row {
+                if let Some(id) = cell.owner {
+                    stats[id].total_army += cell.army_size as i32;
+                    stats[id].total_fields += 1;
+                    if cell.cell_type == CellType::City {
+                        stats[id].total_cities += 1;
+                    }
+                }
+            }
+        }
+        for (id, player_stats) in stats.iter_mut().enumerate() {
+            player_stats.generals_captured = self.generals_captured[id];
+        }
+        stats
+    }
+
+    // Cells that were never seen have `last_update_time == 0` and are refreshed with fog on every update,
+    // the rest keep the state they had when they were last visible
     pub fn update_from(&mut self, other: &GameMap) {
         self.turn = other.turn;
+        self.objective = other.objective.clone();
+        self.winner = other.winner;
+        self.generals_captured = other.generals_captured.clone();
+        let id = self.curr_color;
+        // Hidden walls, camps and barbarians are not revealed, remembered walls are kept until they expire
+        self.walls.retain(|&(expiration, (y, x))| expiration > other.turn && !other.is_visible_to(y, x, id));
+        self.walls.extend(other.walls.iter().filter(|&&(_, (y, x))| other.is_visible_to(y, x, id)));
+        for &(y, x) in &other.barbarian_camps {
+            if other.is_visible_to(y, x, id) && !self.barbarian_camps.contains(&(y, x)) {
+                self.barbarian_camps.push((y, x));
+            }
+        }
+        self.barbarians = other.barbarians.iter().copied().filter(|&(y, x)| other.is_visible_to(y, x, id)).collect();
         for y in 0..self.n {
             for x in 0..self.m {
-                let visible = other.is_visible_to(y, x, self.curr_color);
+                let visible = other.is_visible_to(y, x, id);
                 if visible || self.grid[y][x].last_update_time == 0 {
-                    self.grid[y][x] = other.get_with_fog(y, x, self.curr_color);
+                    self.grid[y][x] = other.get_with_fog(y, x, id);
                 }
             }
         }
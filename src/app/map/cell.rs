@@ -35,6 +35,7 @@ impl GameCell {
     pub fn army_after_time(&self, map: &GameMap, path_len: i64) -> i64 {
         self.army_size + match self.cell_type {
             CellType::City | CellType::General => path_len as i64 + (map.turn - self.last_update_time) as i64,
+            CellType::Mountains if map.rules.destructible_mountains => map.rules.mountain_cost,
             CellType::Empty | CellType::Mountains => 0,
         }
     }
@@ -0,0 +1,8 @@
+#[derive(Default, Clone, Debug)]
+pub struct ObjectiveState {
+    pub hill: Option<(usize, usize)>,
+    pub hill_holder: Option<usize>,
+    pub hill_held_turns: u32,
+    // The flag travels together with the army that captured it
+    pub flag: Option<(usize, usize)>,
+}
@@ -0,0 +1,232 @@
+use super::topology::Topology;
+
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum VisionShape {
+    #[default]
+    Square,
+    Diamond,
+    Circle,
+}
+
+impl VisionShape {
+    pub const ALL: [VisionShape; 3] = [VisionShape::Square, VisionShape::Diamond, VisionShape::Circle];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VisionShape::Square => "Квадрат",
+            VisionShape::Diamond => "Ромб",
+            VisionShape::Circle => "Круг",
+        }
+    }
+
+    pub fn contains(&self, dy: i64, dx: i64, radius: i64) -> bool {
+        match self {
+            VisionShape::Square => dy.abs() <= radius && dx.abs() <= radius,
+            VisionShape::Diamond => dy.abs() + dx.abs() <= radius,
+            // Half a cell of slack so that radius 1 still sees diagonal neighbours
+            VisionShape::Circle => (dy * dy + dx * dx) as f64 <= (radius as f64 + 0.5).powi(2),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Objective {
+    // Capture every enemy general
+    #[default]
+    Conquest,
+    // Hold the central hill for a number of consecutive turns
+    KingOfTheHill,
+    // Bring the neutral flag to your general
+    CaptureTheFlag,
+}
+
+impl Objective {
+    pub const ALL: [Objective; 3] = [Objective::Conquest, Objective::KingOfTheHill, Objective::CaptureTheFlag];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Objective::Conquest => "Захват генералов",
+            Objective::KingOfTheHill => "Царь горы",
+            Objective::CaptureTheFlag => "Захват флага",
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Eq, PartialEq, Debug)]
+pub enum TimeLimit {
+    #[default]
+    Turns,
+    Minutes,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ScoreWeights {
+    pub land: f64,
+    pub army: f64,
+    pub cities: f64,
+    pub generals_captured: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            land: 1.0,
+            army: 1.0,
+            cities: 10.0,
+            generals_captured: 50.0,
+        }
+    }
+}
+
+// Per-player adjustments used to even out matches between players of different skill
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Handicap {
+    // Extra army in every starting general
+    pub starting_army: i64,
+    pub growth_multiplier: i64,
+    pub extra_vision: usize,
+    // The player can't act before this turn
+    pub start_turn: u32,
+}
+
+impl Default for Handicap {
+    fn default() -> Self {
+        Self {
+            starting_army: 0,
+            growth_multiplier: 1,
+            extra_vision: 0,
+            start_turn: 0,
+        }
+    }
+}
+
+impl Handicap {
+    pub fn describe(&self) -> String {
+        let mut parts = vec![];
+        if self.starting_army != 0 {
+            parts.push(format!("+{} армии", self.starting_army));
+        }
+        if self.growth_multiplier != 1 {
+            parts.push(format!("рост x{}", self.growth_multiplier));
+        }
+        if self.extra_vision != 0 {
+            parts.push(format!("обзор +{}", self.extra_vision));
+        }
+        if self.start_turn != 0 {
+            parts.push(format!("старт с хода {}", self.start_turn));
+        }
+        if parts.is_empty() {
+            "нет".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GameRules {
+    pub topology: Topology,
+    // Leaving the map through an edge enters it from the opposite side
+    pub wrap_around: bool,
+    pub vision_radius: usize,
+    pub vision_shape: VisionShape,
+    pub mountains_block_vision: bool,
+    // Capturing a general transfers its stack to the attacker
+    pub leapfrog: bool,
+    // Owned cities grow twice as fast
+    pub city_state: bool,
+    // Land gives no vision, only armies bigger than 1 do
+    pub misty_veil: bool,
+    // Leaderboard is hidden
+    pub silent_war: bool,
+    // Cities see further than the rest of the land
+    pub watchtower: bool,
+    // Border rings of the map turn hostile one by one and then collapse into mountains
+    pub shrinking_zone: bool,
+    pub zone_start_turn: u32,
+    pub zone_shrink_interval: u32,
+    pub objective: Objective,
+    pub hill_hold_turns: u32,
+    // The match ends after a fixed length and players are ranked by score
+    pub timed_match: bool,
+    pub time_limit: TimeLimit,
+    pub turn_limit: u32,
+    pub minutes_limit: u32,
+    pub score_weights: ScoreWeights,
+    // Army can be spent to found cities or raise temporary walls
+    pub buildings: bool,
+    pub city_cost: i64,
+    pub wall_cost: i64,
+    pub wall_duration: u32,
+    // Mountains can be razed by an army bigger than `mountain_cost`
+    pub destructible_mountains: bool,
+    pub mountain_cost: i64,
+    // Generals can relocate to a neighbouring owned cell taking half of their army with them
+    pub mobile_generals: bool,
+    // A player is eliminated only when all of their capitals are captured
+    pub capitals: usize,
+    // Neutral camps periodically send out armies that roam and attack players
+    pub barbarians: bool,
+    pub barbarian_camps: usize,
+    pub barbarian_spawn_interval: u32,
+    pub barbarian_army: i64,
+    // Big stacks too far from an owned city or general lose a unit every turn
+    pub attrition: bool,
+    pub supply_range: usize,
+    pub attrition_min_army: i64,
+    // Indexed by player id, missing entries mean no handicap
+    pub handicaps: Vec<Handicap>,
+}
+
+impl GameRules {
+    pub const WATCHTOWER_BONUS: usize = 2;
+    pub const FLAG_GARRISON: i64 = 10;
+    pub const BARBARIAN_SIGHT: usize = 6;
+
+    pub fn handicap(&self, player_id: usize) -> Handicap {
+        self.handicaps.get(player_id).copied().unwrap_or_default()
+    }
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            topology: Topology::Square,
+            wrap_around: false,
+            vision_radius: 1,
+            vision_shape: VisionShape::Square,
+            mountains_block_vision: false,
+            leapfrog: false,
+            city_state: false,
+            misty_veil: false,
+            silent_war: false,
+            watchtower: false,
+            shrinking_zone: false,
+            zone_start_turn: 150,
+            zone_shrink_interval: 30,
+            objective: Objective::Conquest,
+            hill_hold_turns: 50,
+            timed_match: false,
+            time_limit: TimeLimit::Turns,
+            turn_limit: 300,
+            minutes_limit: 10,
+            score_weights: ScoreWeights::default(),
+            buildings: false,
+            city_cost: 40,
+            wall_cost: 10,
+            wall_duration: 50,
+            destructible_mountains: false,
+            mountain_cost: 30,
+            mobile_generals: false,
+            capitals: 1,
+            barbarians: false,
+            barbarian_camps: 2,
+            barbarian_spawn_interval: 40,
+            barbarian_army: 15,
+            attrition: false,
+            supply_range: 8,
+            attrition_min_army: 10,
+            handicaps: vec![],
+        }
+    }
+}
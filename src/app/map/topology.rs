@@ -0,0 +1,157 @@
+use crate::constants::{DIRECTIONS, DIRECTIONS_WITH_DIAGONALS, HEX_DIRECTIONS_EVEN, HEX_DIRECTIONS_ODD};
+
+use super::rules::VisionShape;
+
+// Height of a pointy-top hexagon and distance between its rows, in hexagon widths
+pub const HEX_HEIGHT: f32 = 1.154_700_5;
+pub const HEX_ROW_SPACING: f32 = 0.866_025_4;
+
+// Hex grids use "odd-r" offset coordinates: odd rows are shifted half a cell to the right
+#[derive(Default, Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Topology {
+    #[default]
+    Square,
+    // Square cells with diagonal moves
+    Octile,
+    Hex,
+}
+
+impl Topology {
+    pub const ALL: [Topology; 3] = [Topology::Square, Topology::Octile, Topology::Hex];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Topology::Square => "Квадраты",
+            Topology::Octile => "Квадраты с диагоналями",
+            Topology::Hex => "Шестиугольники",
+        }
+    }
+
+    pub fn directions(&self, y: usize) -> &'static [(usize, usize)] {
+        match self {
+            Topology::Square => &DIRECTIONS,
+            Topology::Octile => &DIRECTIONS_WITH_DIAGONALS,
+            Topology::Hex if y.is_multiple_of(2) => &HEX_DIRECTIONS_EVEN,
+            Topology::Hex => &HEX_DIRECTIONS_ODD,
+        }
+    }
+
+    pub fn signed_distance(&self, a: (i64, i64), b: (i64, i64)) -> i64 {
+        match self {
+            Topology::Square => (a.0 - b.0).abs() + (a.1 - b.1).abs(),
+            Topology::Octile => (a.0 - b.0).abs().max((a.1 - b.1).abs()),
+            Topology::Hex => {
+                let to_axial = |(y, x): (i64, i64)| (x - (y - (y & 1)) / 2, y);
+                let (q1, r1) = to_axial(a);
+                let (q2, r2) = to_axial(b);
+                let (dq, dr) = (q1 - q2, r1 - r2);
+                (dq.abs() + dr.abs() + (dq + dr).abs()) / 2
+            },
+        }
+    }
+
+    pub fn in_vision_range(&self, shape: VisionShape, from: (i64, i64), to: (i64, i64), radius: i64) -> bool {
+        match self {
+            Topology::Square | Topology::Octile => shape.contains(to.0 - from.0, to.1 - from.1, radius),
+            // Every vision shape looks like a hexagon on a hex grid
+            Topology::Hex => self.signed_distance(from, to) <= radius,
+        }
+    }
+
+    // Width and height of an n x m map in cell widths
+    pub fn world_size(&self, n: usize, m: usize) -> (f32, f32) {
+        match self {
+            Topology::Square | Topology::Octile => (m as f32, n as f32),
+            Topology::Hex => (m as f32 + 0.5, (n as f32 - 1.0) * HEX_ROW_SPACING + HEX_HEIGHT),
+        }
+    }
+
+    // Returns (x, y) of the cell centre in cell widths
+    pub fn cell_center(&self, y: i64, x: i64) -> (f32, f32) {
+        match self {
+            Topology::Square | Topology::Octile => (x as f32 + 0.5, y as f32 + 0.5),
+            Topology::Hex => (
+                x as f32 + 0.5 + 0.5 * (y & 1) as f32,
+                y as f32 * HEX_ROW_SPACING + HEX_HEIGHT * 0.5,
+            ),
+        }
+    }
+
+    // Returns (y, x) of the cell containing the point, which may lie outside of the map
+    pub fn cell_at(&self, px: f32, py: f32) -> (i64, i64) {
+        match self {
+            Topology::Square | Topology::Octile => (py.floor() as i64, px.floor() as i64),
+            Topology::Hex => {
+                // The closest centre always belongs to the hexagon under the point
+                let approx_y = ((py - HEX_HEIGHT * 0.5) / HEX_ROW_SPACING).round() as i64;
+                let mut best = (approx_y, 0);
+                let mut best_dist = f32::MAX;
+                for y in approx_y - 1..=approx_y + 1 {
+                    let approx_x = (px - 0.5 - 0.5 * (y & 1) as f32).round() as i64;
+                    for x in approx_x - 1..=approx_x + 1 {
+                        let (cx, cy) = self.cell_center(y, x);
+                        let dist = (cx - px).powi(2) + (cy - py).powi(2);
+                        if dist < best_dist {
+                            best = (y, x);
+                            best_dist = dist;
+                        }
+                    }
+                }
+                best
+            },
+        }
+    }
+}
+
+// Size and shape of a particular map, answers adjacency and distance queries
+#[derive(Clone, Copy, Debug)]
+pub struct Board {
+    pub topology: Topology,
+    pub n: usize,
+    pub m: usize,
+    pub wrap_around: bool,
+}
+
+impl Board {
+    // Odd rows of a hex grid are shifted, so rows only wrap when their count is even
+    fn wraps_vertically(&self) -> bool {
+        self.wrap_around && (self.topology != Topology::Hex || self.n.is_multiple_of(2))
+    }
+
+    // Maps coordinates that may lie outside of the map to a cell
+    pub fn normalize(&self, (y, x): (i64, i64)) -> Option<(usize, usize)> {
+        let (n, m) = (self.n as i64, self.m as i64);
+        let y = if self.wraps_vertically() { y.rem_euclid(n) } else { y };
+        let x = if self.wrap_around { x.rem_euclid(m) } else { x };
+        if y < 0 || x < 0 || y >= n || x >= m {
+            return None;
+        }
+        Some((y as usize, x as usize))
+    }
+
+    pub fn neighbours(self, y: usize, x: usize) -> impl Iterator<Item = (usize, usize)> {
+        self.topology.directions(y).iter().filter_map(move |&(dy, dx)| {
+            // Directions store -1 as usize::MAX, the casts turn it back into a signed offset
+            let ny = y as i64 + dy as i64;
+            let nx = x as i64 + dx as i64;
+            self.normalize((ny, nx)).filter(|&coords| coords != (y, x))
+        })
+    }
+
+    pub fn signed_distance(&self, a: (i64, i64), b: (i64, i64)) -> i64 {
+        let dys: &[i64] = if self.wraps_vertically() { &[-1, 0, 1] } else { &[0] };
+        let dxs: &[i64] = if self.wrap_around { &[-1, 0, 1] } else { &[0] };
+        let mut res = i64::MAX;
+        for ky in dys {
+            for kx in dxs {
+                let shifted = (b.0 + ky * self.n as i64, b.1 + kx * self.m as i64);
+                res = res.min(self.topology.signed_distance(a, shifted));
+            }
+        }
+        res
+    }
+
+    pub fn distance(&self, a: (usize, usize), b: (usize, usize)) -> usize {
+        self.signed_distance((a.0 as i64, a.1 as i64), (b.0 as i64, b.1 as i64)) as usize
+    }
+}
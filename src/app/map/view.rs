@@ -0,0 +1,37 @@
+use super::{GameMap, PlayerStatistics};
+
+// Everything a player is allowed to know about the game, built by the engine every turn
+#[derive(Clone)]
+pub struct PlayerView {
+    pub player_id: usize,
+    pub turn: u32,
+    // Visible cells are up to date, the rest keep the state they had when they were last seen
+    pub map: GameMap,
+    pub visible: Vec<Vec<bool>>,
+    pub stats: PlayerStatistics,
+    // Statistics of every player indexed by id, hidden in silent war
+    pub leaderboard: Option<Vec<PlayerStatistics>>,
+}
+
+impl PlayerView {
+    // `memory` must already be updated from `game` for the current turn
+    pub fn new(game: &GameMap, memory: &GameMap) -> PlayerView {
+        let player_id = memory.curr_color;
+        let visible = (0..game.n)
+            .map(|y| (0..game.m).map(|x| game.is_visible_to(y, x, player_id)).collect())
+            .collect();
+        let stats = game.get_statistics();
+        PlayerView {
+            player_id,
+            turn: game.turn,
+            map: memory.clone(),
+            visible,
+            stats: stats[player_id].clone(),
+            leaderboard: (!game.rules.silent_war).then_some(stats),
+        }
+    }
+
+    pub fn is_visible(&self, y: usize, x: usize) -> bool {
+        self.visible[y][x]
+    }
+}
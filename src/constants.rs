@@ -24,7 +24,38 @@ pub const PLAYER_COLORS: [Color; 16] = [
     color_u8!(114, 102, 63, 255),
 ];
 
+pub const BARBARIAN_COLOR: Color = color_u8!(96, 72, 48, 255);
+
 pub const DIRECTIONS: [(usize, usize); 4] =
     [((-1i32) as usize, 0), (0, (-1i32) as usize), (0, 1), (1, 0)];
 
+pub const DIRECTIONS_WITH_DIAGONALS: [(usize, usize); 8] = [
+    ((-1i32) as usize, (-1i32) as usize),
+    ((-1i32) as usize, 0),
+    ((-1i32) as usize, 1),
+    (0, (-1i32) as usize),
+    (0, 1),
+    (1, (-1i32) as usize),
+    (1, 0),
+    (1, 1),
+];
+
+// Neighbours on a hex grid where odd rows are shifted to the right
+pub const HEX_DIRECTIONS_EVEN: [(usize, usize); 6] = [
+    ((-1i32) as usize, (-1i32) as usize),
+    ((-1i32) as usize, 0),
+    (0, (-1i32) as usize),
+    (0, 1),
+    (1, (-1i32) as usize),
+    (1, 0),
+];
+pub const HEX_DIRECTIONS_ODD: [(usize, usize); 6] = [
+    ((-1i32) as usize, 0),
+    ((-1i32) as usize, 1),
+    (0, (-1i32) as usize),
+    (0, 1),
+    (1, 0),
+    (1, 1),
+];
+
 pub const DELAY_BETWEEN_TICKS: f64 = 0.2;